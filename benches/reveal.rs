@@ -0,0 +1,34 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use minesweeperrust::service::{Game, Options};
+
+fn generated_game(rows: i32, cols: i32, mines: i32) -> Game {
+    let options = Options {
+        seed: Some(1),
+        ..Default::default()
+    };
+    let mut game = Game::with_options(rows, cols, mines, options);
+    let point = game.new_point((rows / 2, cols / 2)).unwrap();
+    game.generate_at(point).unwrap();
+    game
+}
+
+fn reveal_sparse(c: &mut Criterion) {
+    for (rows, cols, mines) in [(100, 100, 10), (1000, 1000, 100)] {
+        let game = generated_game(rows, cols, mines);
+        let name = format!("reveal {}x{} with {} mines", rows, cols, mines);
+        c.bench_function(&name, |b| {
+            b.iter_batched(
+                || game.clone(),
+                |mut g| {
+                    let point = g.new_point((rows / 2, cols / 2)).unwrap();
+                    g.reveal_at(point);
+                    g.is_all_revealed()
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+}
+
+criterion_group!(benches, reveal_sparse);
+criterion_main!(benches);
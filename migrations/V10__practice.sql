@@ -0,0 +1,8 @@
+-- Practice games can undo and redo moves, which needs every move kept in order. Each
+-- `history` entry has `kind` 0 reveal, 1 chord, 2 flag, 3 question, the cell, `created` in
+-- microseconds since the epoch, `state` the game state right after the move and whether
+-- it was `undone`.
+ALTER TABLE games
+    ADD COLUMN practice BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN undos INT NOT NULL DEFAULT 0,
+    ADD COLUMN history JSONB NOT NULL DEFAULT '[]';
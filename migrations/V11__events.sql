@@ -0,0 +1,22 @@
+-- Games are stored as an append-only log of events plus a snapshot in `games`, which now
+-- records the sequence number of the last event it includes. `kind` is 0 created,
+-- 1 revealed, 2 chorded, 3 flagged, 4 questioned, 5 paused, 6 resumed, 7 undone,
+-- 8 redone, 9 hint taken, 10 timed out, 11 revealed before play; the cell is only set
+-- for events on a cell.
+ALTER TABLE games
+    ADD COLUMN version INT NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS game_events (
+    game_id UUID NOT NULL REFERENCES games (id) ON DELETE CASCADE,
+    seq INT NOT NULL,
+    kind INT NOT NULL,
+    cell_row INT,
+    cell_col INT,
+    created TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (game_id, seq)
+);
+
+-- Existing rows become snapshots at version 0, opening their log with the creation.
+INSERT INTO game_events (game_id, seq, kind, created)
+    SELECT id, 0, 0, created FROM games
+    ON CONFLICT DO NOTHING;
@@ -0,0 +1,10 @@
+-- Daily challenge attempts; a player gets one attempt at each day's board of a difficulty
+-- (0 beginner, 1 intermediate, 2 expert).
+ALTER TABLE games
+    ADD COLUMN daily_date DATE,
+    ADD COLUMN daily_difficulty INT,
+    ADD COLUMN player TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS games_daily_player
+    ON games (daily_date, daily_difficulty, player)
+    WHERE daily_date IS NOT NULL;
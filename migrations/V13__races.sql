@@ -0,0 +1,22 @@
+-- Races: games on one seeded layout, one per player slot. A slot is open while its
+-- player is null.
+CREATE TABLE IF NOT EXISTS races (
+    id UUID PRIMARY KEY NOT NULL,
+    created TIMESTAMPTZ NOT NULL,
+    rows INT NOT NULL,
+    cols INT NOT NULL,
+    mines INT NOT NULL,
+    seed BIGINT NOT NULL,
+    finished TIMESTAMPTZ
+);
+
+ALTER TABLE games
+    ADD COLUMN race_id UUID REFERENCES races (id) ON DELETE CASCADE;
+
+CREATE TABLE IF NOT EXISTS race_participants (
+    game_id UUID PRIMARY KEY REFERENCES games (id) ON DELETE CASCADE,
+    race_id UUID NOT NULL REFERENCES races (id) ON DELETE CASCADE,
+    player TEXT,
+    joined TIMESTAMPTZ,
+    UNIQUE (race_id, player)
+);
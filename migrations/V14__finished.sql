@@ -0,0 +1,9 @@
+-- When a game was won, lost or timed out, to order race winners by who finished first.
+-- Games already over take the time of their last logged event.
+ALTER TABLE games
+    ADD COLUMN finished TIMESTAMPTZ;
+
+UPDATE games SET finished = (
+        SELECT max(created) FROM game_events e WHERE e.game_id = games.id
+    )
+    WHERE state IN (3, 4, 5);
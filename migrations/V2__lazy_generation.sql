@@ -0,0 +1,9 @@
+ALTER TABLE games
+    ADD COLUMN mines INT NOT NULL DEFAULT 0,
+    ADD COLUMN safe_area INT NOT NULL DEFAULT 0;
+
+UPDATE games SET mines = (
+    SELECT count(*)
+    FROM jsonb_array_elements(board->'rows') AS r, jsonb_array_elements_text(r) AS c
+    WHERE c = 'x'
+);
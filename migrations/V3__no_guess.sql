@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN no_guess BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+-- rng_version 0 marks boards generated before layouts were seeded.
+ALTER TABLE games
+    ADD COLUMN seed BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN rng_version INT NOT NULL DEFAULT 0;
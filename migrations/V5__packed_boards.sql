@@ -0,0 +1,64 @@
+-- Boards move from JSONB to bit-packed BYTEA: one bit per cell for mines and two bits per
+-- cell for the player state (0 hidden, 1 revealed, 2 flag, 3 question), row-major with the
+-- first cell in the least significant bits. Cells without mines are recomputed on load.
+ALTER TABLE games
+    ADD COLUMN rows INT NOT NULL DEFAULT 0,
+    ADD COLUMN cols INT NOT NULL DEFAULT 0,
+    ADD COLUMN board_bits BYTEA DEFAULT NULL,
+    ADD COLUMN player_bits BYTEA DEFAULT NULL,
+    ALTER COLUMN board DROP NOT NULL,
+    ALTER COLUMN player_board DROP NOT NULL;
+
+CREATE FUNCTION pack_codes(codes INT[], bits INT) RETURNS BYTEA AS $$
+DECLARE
+    per_byte INT := 8 / bits;
+    packed BYTEA;
+BEGIN
+    IF coalesce(array_length(codes, 1), 0) = 0 THEN
+        RETURN NULL;
+    END IF;
+    packed := decode(repeat('00', (array_length(codes, 1) + per_byte - 1) / per_byte), 'hex');
+    FOR i IN 0 .. array_length(codes, 1) - 1 LOOP
+        packed := set_byte(packed, i / per_byte,
+            get_byte(packed, i / per_byte) | (codes[i + 1] << ((i % per_byte) * bits)));
+    END LOOP;
+    RETURN packed;
+END;
+$$ LANGUAGE plpgsql IMMUTABLE;
+
+-- Flattens either the legacy `{"rows": [[char]]}` or the typed `{"columns", "cells"}` layout
+-- into cell codes, using `legacy` to translate characters and `typed` for integers.
+CREATE FUNCTION board_codes(board JSONB, legacy JSONB, typed JSONB) RETURNS INT[] AS $$
+    SELECT CASE WHEN board ? 'rows' THEN
+        ARRAY(
+            SELECT coalesce((legacy->>c)::INT, 0)
+            FROM jsonb_array_elements(board->'rows') WITH ORDINALITY AS r(rw, i),
+                 jsonb_array_elements_text(r.rw) WITH ORDINALITY AS e(c, j)
+            ORDER BY i, j
+        )
+    ELSE
+        ARRAY(
+            SELECT coalesce((typed->>c)::INT, 0)
+            FROM jsonb_array_elements_text(board->'cells') WITH ORDINALITY AS e(c, i)
+            ORDER BY i
+        )
+    END
+$$ LANGUAGE sql IMMUTABLE;
+
+UPDATE games SET
+    rows = CASE WHEN player_board ? 'rows'
+        THEN jsonb_array_length(player_board->'rows')
+        ELSE jsonb_array_length(player_board->'cells') / (player_board->>'columns')::INT
+    END,
+    cols = CASE WHEN player_board ? 'rows'
+        THEN jsonb_array_length(player_board->'rows'->0)
+        ELSE (player_board->>'columns')::INT
+    END,
+    board_bits = pack_codes(board_codes(board, '{"x": 1}', '{"-1": 1}'), 1),
+    player_bits = pack_codes(
+        board_codes(player_board, '{"v": 1, "!": 2, "?": 3}', '{"1": 1, "2": 2, "3": 3}'), 2),
+    board = NULL,
+    player_board = NULL;
+
+DROP FUNCTION board_codes(JSONB, JSONB, JSONB);
+DROP FUNCTION pack_codes(INT[], INT);
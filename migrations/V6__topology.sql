@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN topology INT NOT NULL DEFAULT 0;
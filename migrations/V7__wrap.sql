@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN wrap BOOLEAN NOT NULL DEFAULT FALSE;
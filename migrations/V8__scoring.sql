@@ -0,0 +1,7 @@
+-- 3BV of the generated layout and successful player moves per type, used for scoring.
+ALTER TABLE games
+    ADD COLUMN bbbv INT NOT NULL DEFAULT 0,
+    ADD COLUMN reveal_clicks INT NOT NULL DEFAULT 0,
+    ADD COLUMN chord_clicks INT NOT NULL DEFAULT 0,
+    ADD COLUMN flag_clicks INT NOT NULL DEFAULT 0,
+    ADD COLUMN question_clicks INT NOT NULL DEFAULT 0;
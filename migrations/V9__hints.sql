@@ -0,0 +1,2 @@
+-- Hints given during the game, each one lowers the final score.
+ALTER TABLE games ADD COLUMN hints INT NOT NULL DEFAULT 0;
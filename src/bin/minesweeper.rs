@@ -1,9 +1,14 @@
 use axum::{http::StatusCode, Router};
 use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
-use minesweeperrust::{database::Database, handler, service::Service};
+use minesweeperrust::database::{Database, Listener};
+use minesweeperrust::{handler, service::Service};
 use std::env;
+use std::time::Duration;
 use tokio::signal;
 
+/// Longest wait between attempts to listen for game updates again.
+const MAX_RELAY_BACKOFF: Duration = Duration::from_secs(30);
+
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt()
@@ -14,22 +19,45 @@ async fn main() {
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let port = env::var("PORT").unwrap_or("8080".to_string());
     let metrics_port = env::var("METRICS_PORT").unwrap_or("8081".to_string());
+    let sweep_seconds: u64 = env::var("TIMEOUT_SWEEP_SECONDS")
+        .map(|v| v.parse().expect("TIMEOUT_SWEEP_SECONDS must be a number"))
+        .unwrap_or(60);
+    let pool_size: usize = env::var("DATABASE_POOL_SIZE")
+        .map(|v| v.parse().expect("DATABASE_POOL_SIZE must be a number"))
+        .unwrap_or(16);
+    let daily_secret = env::var("DAILY_SECRET").expect("DAILY_SECRET must be set");
+    let defaults = handler::Limits::default();
+    let limits = handler::Limits {
+        max_rows: env::var("MAX_ROWS")
+            .map(|v| v.parse().expect("MAX_ROWS must be a number"))
+            .unwrap_or(defaults.max_rows),
+        max_columns: env::var("MAX_COLUMNS")
+            .map(|v| v.parse().expect("MAX_COLUMNS must be a number"))
+            .unwrap_or(defaults.max_columns),
+    };
 
-    let (mut client, conn) = tokio_postgres::connect(&database_url, tokio_postgres::NoTls)
-        .await
+    let config: tokio_postgres::Config = database_url
+        .parse()
+        .expect("DATABASE_URL must be a connection string");
+    let manager = deadpool_postgres::Manager::new(config, tokio_postgres::NoTls);
+    let pool = deadpool_postgres::Pool::builder(manager)
+        .max_size(pool_size)
+        .build()
         .expect("error creating connection pool");
-    tokio::spawn(async move {
-        if let Err(e) = conn.await {
-            eprintln!("connection error: {}", e);
-        }
-    });
 
+    let mut client = pool.get().await.expect("error connecting to the database");
     if let Err(e) = Database::run_migrations(&mut client).await {
         eprintln!("error running migrations: {}", e);
     };
-    let db = Database::new(client);
-    let service = Service::new(db);
-    let handler = handler::Handler::new(service);
+    drop(client);
+    let db = Database::new(pool);
+    tokio::spawn(sweep_timeouts(db.clone(), sweep_seconds));
+    let service = Service::new(db).with_daily_secret(daily_secret);
+    let listener = Listener::connect(&database_url)
+        .await
+        .expect("error listening for game updates");
+    tokio::spawn(relay_updates(listener, database_url, service.clone()));
+    let handler = handler::Handler::new(service).with_limits(limits);
     let router = handler
         .router()
         .route_layer(axum::middleware::from_fn(track_metrics))
@@ -66,6 +94,42 @@ async fn main() {
     );
 }
 
+async fn sweep_timeouts(db: Database, period_seconds: u64) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(period_seconds));
+    loop {
+        interval.tick().await;
+        match db.timeout_expired().await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("timed out {} games", n),
+            Err(e) => tracing::error!("error sweeping timed out games: {}", e),
+        }
+    }
+}
+
+/// Forwards the games changed by any instance to the subscribers of this one. When the
+/// listener connection drops it connects and listens again, doubling the wait between
+/// failed attempts up to `MAX_RELAY_BACKOFF`.
+async fn relay_updates(mut listener: Listener, database_url: String, service: Service<Database>) {
+    loop {
+        while let Some(id) = listener.recv().await {
+            if let Err(e) = service.relay(id).await {
+                tracing::error!("error relaying update of game {}: {}", id, e);
+            }
+        }
+        tracing::error!("game update listener closed, reconnecting");
+        let mut backoff = Duration::from_secs(1);
+        listener = loop {
+            tokio::time::sleep(backoff).await;
+            match Listener::connect(&database_url).await {
+                Ok(listener) => break listener,
+                Err(e) => tracing::error!("error listening for game updates: {}", e),
+            }
+            backoff = (backoff * 2).min(MAX_RELAY_BACKOFF);
+        };
+        tracing::info!("listening for game updates again");
+    }
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -90,6 +154,7 @@ async fn shutdown_signal() {
     }
 }
 
+#[allow(clippy::let_and_return)]
 async fn setup_metrics_app() -> Router {
     const EXPONENTIAL_SECONDS: &[f64] = &[
         0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
@@ -101,6 +166,11 @@ async fn setup_metrics_app() -> Router {
             EXPONENTIAL_SECONDS,
         )
         .unwrap()
+        .set_buckets_for_metric(
+            Matcher::Full("board_generation_duration_seconds".to_string()),
+            EXPONENTIAL_SECONDS,
+        )
+        .unwrap()
         .install_recorder()
         .unwrap();
 
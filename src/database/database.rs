@@ -1,9 +1,71 @@
+use crate::database::listener::GAME_UPDATES;
+use crate::database::model::map_from_events;
 use crate::database::model::map_from_model;
+use crate::database::model::map_to_events;
 use crate::database::model::map_to_model;
+use crate::database::model::state_to_i32;
+use crate::database::model::Logged;
 use crate::service::DatabaseTrait;
-use crate::service::{Error, Game};
+use crate::service::{DailyResult, Error, Event, Game, Participant, Race, State};
 use axum::async_trait;
-use std::sync::Arc;
+use deadpool_postgres::GenericClient;
+use tokio_postgres::error::SqlState;
+
+/// Events logged between two snapshots of a game; finished games are snapshotted too.
+const SNAPSHOT_EVERY: i32 = 20;
+
+/// Whether `events` hold the game's first reveal, the move that places the mines.
+fn generates_layout(game: &Game, events: &[crate::service::Logged]) -> bool {
+    let reveals = events
+        .iter()
+        .filter(|l| matches!(l.event, Event::CellRevealed(_)))
+        .count();
+    reveals > 0 && game.clicks.reveal as usize == reveals
+}
+
+/// Reads a snapshot row selected with every column of `games`.
+fn game_from_row(row: &tokio_postgres::Row) -> Game {
+    let game = crate::database::model::Game {
+        id: row.get("id"),
+        created: row.get("created"),
+        updated: row.get("updated"),
+        title: row.get("title"),
+        board: row.get("board"),
+        player_board: row.get("player_board"),
+        rows: row.get("rows"),
+        cols: row.get("cols"),
+        board_bits: row.get("board_bits"),
+        player_bits: row.get("player_bits"),
+        mines: row.get("mines"),
+        safe_area: row.get("safe_area"),
+        no_guess: row.get("no_guess"),
+        topology: row.get("topology"),
+        wrap: row.get("wrap"),
+        seed: row.get("seed"),
+        rng_version: row.get("rng_version"),
+        state: row.get("state"),
+        duration_seconds: row.get("duration_seconds"),
+        elapsed_seconds: row.get("elapsed_seconds"),
+        score: row.get("score"),
+        resumed_timestamp: row.get("resumed_timestamp"),
+        bbbv: row.get("bbbv"),
+        reveal_clicks: row.get("reveal_clicks"),
+        chord_clicks: row.get("chord_clicks"),
+        flag_clicks: row.get("flag_clicks"),
+        question_clicks: row.get("question_clicks"),
+        hints: row.get("hints"),
+        practice: row.get("practice"),
+        undos: row.get("undos"),
+        version: row.get("version"),
+        daily_date: row.get("daily_date"),
+        daily_difficulty: row.get("daily_difficulty"),
+        player: row.get("player"),
+        race_id: row.get("race_id"),
+        history: row.get("history"),
+        finished: row.get("finished"),
+    };
+    map_from_model(game)
+}
 
 mod embedded {
     use refinery::embed_migrations;
@@ -12,40 +74,353 @@ mod embedded {
 
 #[async_trait]
 impl DatabaseTrait for Database {
+    /// Stores the game and opens its log in one transaction.
     async fn insert(&self, game: Game) -> Result<(), Error> {
-        let value = map_to_model(game);
-        self.client.execute(
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+        Self::insert_game(&tx, game).await?;
+        tx.commit().await?;
+        Result::Ok(())
+    }
+
+    /// Appends the game's new events to its log, writing a snapshot only every
+    /// `SNAPSHOT_EVERY` events, when the game ends or when its layout was just generated,
+    /// so reads never place the mines again. Games changed without events are
+    /// snapshotted right away. Two writers appending to the same game fail on the log's key
+    /// with `Error::WriteConflict`.
+    /// Listeners on `GAME_UPDATES` are notified once it is stored. Everything is written in
+    /// one transaction.
+    async fn update(&self, mut game: Game) -> Result<(), Error> {
+        let events = std::mem::take(&mut game.events);
+        let previous = game.version;
+        game.version += events.len() as i32;
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+        Self::append_events(&tx, game.id, map_to_events(&events, previous + 1)).await?;
+        let (id, over) = (game.id, game.state.is_over());
+        if events.is_empty()
+            || over
+            || generates_layout(&game, &events)
+            || game.version / SNAPSHOT_EVERY > previous / SNAPSHOT_EVERY
+        {
+            Self::save_snapshot(&tx, game).await?;
+        }
+        Self::notify(&tx, id).await?;
+        tx.commit().await?;
+        Result::Ok(())
+    }
+
+    /// Reads the latest snapshot and replays the events logged after it, returning the
+    /// connection to the pool before rebuilding the game.
+    async fn get(&self, game_id: uuid::Uuid) -> Result<Game, Error> {
+        let (row, events) = {
+            let client = self.pool.get().await?;
+            let row = Self::get_snapshot(&client, game_id).await?;
+            let events = Self::get_events(&client, game_id, row.get("version")).await?;
+            (row, events)
+        };
+        let mut g = game_from_row(&row);
+        g.apply_events(&map_from_events(&g, &events))?;
+        if let Some(last) = events.last() {
+            g.version = last.seq;
+        }
+        Result::Ok(g)
+    }
+
+    async fn events(&self, game: &Game) -> Result<Vec<crate::service::Logged>, Error> {
+        let client = self.pool.get().await?;
+        let events = Self::get_events(&client, game.id, -1).await?;
+        Result::Ok(map_from_events(game, &events))
+    }
+
+    /// Finished games are always snapshotted, so their rows are up to date.
+    async fn daily_results(&self, date: time::Date) -> Result<Vec<DailyResult>, Error> {
+        let rows = self.pool.get().await?.query(
             r##"
-                INSERT INTO games
-                    (id, created, updated, title, board, player_board, state, duration_seconds, elapsed_seconds, score, resumed_timestamp) 
+                SELECT
+                    id, created, updated, title, board, player_board, rows, cols, board_bits, player_bits, mines, safe_area, no_guess, topology, wrap, seed, rng_version, state, duration_seconds, elapsed_seconds, score, resumed_timestamp, bbbv, reveal_clicks, chord_clicks, flag_clicks, question_clicks, hints, practice, undos, version, daily_date, daily_difficulty, player, race_id, history, finished
+                FROM
+                    games
+                WHERE
+                    daily_date = $1 AND state IN ($2, $3, $4)
+            "##,
+            &[&date, &state_to_i32(State::Won), &state_to_i32(State::Lost), &state_to_i32(State::Timeout)])
+            .await?;
+        Result::Ok(
+            rows.iter()
+                .map(game_from_row)
+                .filter_map(|g| {
+                    let daily = g.daily.clone()?;
+                    Some(DailyResult {
+                        game_id: Some(g.id),
+                        player: daily.player,
+                        difficulty: daily.difficulty,
+                        state: g.state,
+                        score: g.score,
+                        elapsed_seconds: g.elapsed_seconds,
+                        revealed: g.revealed(),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// The race row goes first since its games point to it, and the slots last, all in
+    /// one transaction.
+    async fn insert_race(&self, race: Race, games: Vec<Game>) -> Result<(), Error> {
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+        tx.execute(
+            r##"
+                INSERT INTO races
+                    (id, created, rows, cols, mines, seed, finished)
                 VALUES
-                    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                    ($1, $2, $3, $4, $5, $6, $7)
+            "##,
+            &[
+                &race.id,
+                &race.created,
+                &race.rows,
+                &race.cols,
+                &race.mines,
+                &(race.seed as i64),
+                &race.finished,
+            ],
+        )
+        .await?;
+        for game in games {
+            Self::insert_game(&tx, game).await?;
+        }
+        let game_ids: Vec<uuid::Uuid> = race.participants.iter().map(|p| p.game_id).collect();
+        tx.execute(
+            r##"
+                INSERT INTO race_participants
+                    (game_id, race_id)
+                SELECT
+                    UNNEST($1::UUID[]), $2
+            "##,
+            &[&game_ids, &race.id],
+        )
+        .await?;
+        tx.commit().await?;
+        Result::Ok(())
+    }
+
+    /// Slots come in the order players joined, open ones last.
+    async fn get_race(&self, race_id: uuid::Uuid) -> Result<Race, Error> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                r##"
+                SELECT
+                    id, created, rows, cols, mines, seed, finished
+                FROM
+                    races
+                WHERE
+                    id = $1
             "##,
-            &[&value.id, &value.created, &value.updated, &value.title, &value.board, &value.player_board, &value.state, &value.duration_seconds, &value.elapsed_seconds, &value.score, &value.resumed_timestamp])
+                &[&race_id],
+            )
+            .await?;
+        if rows.is_empty() {
+            return Result::Err(Error::NotFound {
+                id: race_id.to_string(),
+            });
+        }
+        let participants = client
+            .query(
+                r##"
+                SELECT
+                    game_id, player, joined
+                FROM
+                    race_participants
+                WHERE
+                    race_id = $1
+                ORDER BY
+                    joined NULLS LAST, game_id
+            "##,
+                &[&race_id],
+            )
+            .await?
+            .iter()
+            .map(|row| Participant {
+                game_id: row.get("game_id"),
+                player: row.get("player"),
+                joined: row.get("joined"),
+            })
+            .collect();
+        Result::Ok(Race {
+            id: rows[0].get("id"),
+            created: rows[0].get("created"),
+            rows: rows[0].get("rows"),
+            cols: rows[0].get("cols"),
+            mines: rows[0].get("mines"),
+            seed: rows[0].get::<_, i64>("seed") as u64,
+            finished: rows[0].get("finished"),
+            participants,
+        })
+    }
+
+    /// Two players joining at once get different slots; a taken name fails on the
+    /// race's unique player constraint.
+    async fn join_race(&self, race_id: uuid::Uuid, player: String) -> Result<uuid::Uuid, Error> {
+        let rows = self
+            .pool
+            .get()
+            .await?
+            .query(
+                r##"
+                UPDATE
+                    race_participants
+                SET
+                    player = $2, joined = $3
+                WHERE
+                    game_id = (
+                        SELECT game_id FROM race_participants
+                        WHERE race_id = $1 AND player IS NULL
+                        ORDER BY game_id
+                        LIMIT 1
+                        FOR UPDATE SKIP LOCKED
+                    )
+                RETURNING
+                    game_id
+            "##,
+                &[&race_id, &player, &time::OffsetDateTime::now_utc()],
+            )
+            .await
+            .map_err(|e| match e.code() {
+                Some(&SqlState::UNIQUE_VIOLATION) => Error::PlayerAlreadyJoined {
+                    player: player.clone(),
+                    id: race_id.to_string(),
+                },
+                _ => e.into(),
+            })?;
+        match rows.first() {
+            Some(row) => Result::Ok(row.get("game_id")),
+            None => Result::Err(Error::RaceFull {
+                id: race_id.to_string(),
+            }),
+        }
+    }
+
+    async fn finish_race(
+        &self,
+        race_id: uuid::Uuid,
+        finished: time::OffsetDateTime,
+    ) -> Result<(), Error> {
+        self.pool
+            .get()
+            .await?
+            .execute(
+                "UPDATE races SET finished = $2 WHERE id = $1 AND finished IS NULL",
+                &[&race_id, &finished],
+            )
             .await?;
         Result::Ok(())
     }
+}
+
+/// Each call takes a connection of its own from the pool, so a transaction never takes
+/// in statements from other requests.
+#[derive(Clone)]
+pub struct Database {
+    pool: deadpool_postgres::Pool,
+}
+
+impl Database {
+    pub fn new(pool: deadpool_postgres::Pool) -> Self {
+        Self { pool }
+    }
 
-    async fn update(&self, game: Game) -> Result<(), Error> {
+    /// Stores the game as its first snapshot, opening its log with `GameCreated`.
+    async fn insert_game(client: &impl GenericClient, mut game: Game) -> Result<(), Error> {
+        let mut events = vec![crate::service::Logged {
+            event: Event::GameCreated,
+            created: game.created,
+        }];
+        events.append(&mut game.events);
+        game.version = events.len() as i32 - 1;
+        let events = map_to_events(&events, 0);
         let value = map_to_model(game);
-        self.client.execute(
+        client.execute(
+            r##"
+                INSERT INTO games
+                    (id, created, updated, title, board, player_board, rows, cols, board_bits, player_bits, mines, safe_area, no_guess, topology, wrap, seed, rng_version, state, duration_seconds, elapsed_seconds, score, resumed_timestamp, bbbv, reveal_clicks, chord_clicks, flag_clicks, question_clicks, hints, practice, undos, version, daily_date, daily_difficulty, player, race_id, history, finished)
+                VALUES
+                    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36, $37)
+            "##,
+            &[&value.id, &value.created, &value.updated, &value.title, &value.board, &value.player_board, &value.rows, &value.cols, &value.board_bits, &value.player_bits, &value.mines, &value.safe_area, &value.no_guess, &value.topology, &value.wrap, &value.seed, &value.rng_version, &value.state, &value.duration_seconds, &value.elapsed_seconds, &value.score, &value.resumed_timestamp, &value.bbbv, &value.reveal_clicks, &value.chord_clicks, &value.flag_clicks, &value.question_clicks, &value.hints, &value.practice, &value.undos, &value.version, &value.daily_date, &value.daily_difficulty, &value.player, &value.race_id, &value.history, &value.finished])
+            .await
+            .map_err(|e| match (e.code(), &value.player, value.daily_date) {
+                (Some(&SqlState::UNIQUE_VIOLATION), Some(player), Some(date)) => {
+                    Error::DailyAlreadyPlayed {
+                        player: player.clone(),
+                        date: date.to_string(),
+                    }
+                }
+                _ => e.into(),
+            })?;
+        Self::append_events(client, value.id, events).await?;
+        Self::notify(client, value.id).await
+    }
+
+    /// Events logged for a game after sequence number `after`, oldest first.
+    async fn get_events(
+        client: &impl GenericClient,
+        game_id: uuid::Uuid,
+        after: i32,
+    ) -> Result<Vec<Logged>, Error> {
+        let events = client
+            .query(
+                r##"
+                SELECT
+                    seq, kind, cell_row, cell_col, created
+                FROM
+                    game_events
+                WHERE
+                    game_id = $1 AND seq > $2
+                ORDER BY
+                    seq
+            "##,
+                &[&game_id, &after],
+            )
+            .await?
+            .iter()
+            .map(|row| Logged {
+                seq: row.get("seq"),
+                kind: row.get("kind"),
+                cell_row: row.get("cell_row"),
+                cell_col: row.get("cell_col"),
+                created: row.get("created"),
+            })
+            .collect();
+        Result::Ok(events)
+    }
+
+    async fn save_snapshot(client: &impl GenericClient, game: Game) -> Result<(), Error> {
+        let value = map_to_model(game);
+        client.execute(
             r##"
                 UPDATE games SET
-                    (created, updated, title, board, player_board, state, duration_seconds, elapsed_seconds, score, resumed_timestamp)
-                    = ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                    (created, updated, title, board, player_board, rows, cols, board_bits, player_bits, mines, safe_area, no_guess, topology, wrap, seed, rng_version, state, duration_seconds, elapsed_seconds, score, resumed_timestamp, bbbv, reveal_clicks, chord_clicks, flag_clicks, question_clicks, hints, practice, undos, version, daily_date, daily_difficulty, player, history, finished)
+                    = ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35)
                 WHERE
-                    id = $11
+                    id = $36
             "##,
-            &[&value.created, &value.updated, &value.title, &value.board, &value.player_board, &value.state, &value.duration_seconds, &value.elapsed_seconds, &value.score, &value.resumed_timestamp, &value.id])
+            &[&value.created, &value.updated, &value.title, &value.board, &value.player_board, &value.rows, &value.cols, &value.board_bits, &value.player_bits, &value.mines, &value.safe_area, &value.no_guess, &value.topology, &value.wrap, &value.seed, &value.rng_version, &value.state, &value.duration_seconds, &value.elapsed_seconds, &value.score, &value.resumed_timestamp, &value.bbbv, &value.reveal_clicks, &value.chord_clicks, &value.flag_clicks, &value.question_clicks, &value.hints, &value.practice, &value.undos, &value.version, &value.daily_date, &value.daily_difficulty, &value.player, &value.history, &value.finished, &value.id])
             .await?;
         Result::Ok(())
     }
 
-    async fn get(&self, game_id: uuid::Uuid) -> Result<Game, Error> {
-        let rows = self.client.query(
+    /// The `games` row of the game, to read with `game_from_row`.
+    async fn get_snapshot(
+        client: &impl GenericClient,
+        game_id: uuid::Uuid,
+    ) -> Result<tokio_postgres::Row, Error> {
+        let mut rows = client.query(
             r##"
                 SELECT
-                    id, created, updated, title, board, player_board, state, duration_seconds, elapsed_seconds, score, resumed_timestamp
+                    id, created, updated, title, board, player_board, rows, cols, board_bits, player_bits, mines, safe_area, no_guess, topology, wrap, seed, rng_version, state, duration_seconds, elapsed_seconds, score, resumed_timestamp, bbbv, reveal_clicks, chord_clicks, flag_clicks, question_clicks, hints, practice, undos, version, daily_date, daily_difficulty, player, race_id, history, finished
                 FROM
                     games
                 WHERE
@@ -58,33 +433,99 @@ impl DatabaseTrait for Database {
                 id: game_id.to_string(),
             });
         }
-        Result::Ok(map_from_model(crate::database::model::Game {
-            id: rows[0].get("id"),
-            created: rows[0].get("created"),
-            updated: rows[0].get("updated"),
-            title: rows[0].get("title"),
-            board: rows[0].get("board"),
-            player_board: rows[0].get("player_board"),
-            state: rows[0].get("state"),
-            duration_seconds: rows[0].get("duration_seconds"),
-            elapsed_seconds: rows[0].get("elapsed_seconds"),
-            score: rows[0].get("score"),
-            resumed_timestamp: rows[0].get("resumed_timestamp"),
-        }))
+        Result::Ok(rows.swap_remove(0))
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct Database {
-    client: Arc<tokio_postgres::Client>,
-}
+    /// Marks started games whose time limit ran out while nobody was playing them as timed out,
+    /// returning how many were updated. Only games started as of their snapshot can have run
+    /// out: the first reveal is always snapshotted and paused games stop the clock. Each is
+    /// rebuilt from its log before checking, as the snapshot may be behind, and one that
+    /// fails is logged and skipped so the rest are still swept.
+    pub async fn timeout_expired(&self) -> Result<u64, Error> {
+        let rows = self.pool.get().await?.query(
+            r##"
+                SELECT
+                    id
+                FROM
+                    games
+                WHERE
+                    duration_seconds > 0
+                    AND state = $1
+                    AND (
+                        resumed_timestamp + make_interval(secs => duration_seconds - elapsed_seconds) <= now()
+                        OR EXISTS (SELECT 1 FROM game_events e WHERE e.game_id = games.id AND e.seq > games.version)
+                    )
+            "##,
+            &[&state_to_i32(State::Started)])
+            .await?;
+        let mut updated = 0;
+        for row in rows {
+            let id: uuid::Uuid = row.get("id");
+            match self.expire(id).await {
+                Ok(true) => updated += 1,
+                Ok(false) => {}
+                Err(e) => tracing::error!("error timing out game {}: {}", id, e),
+            }
+        }
+        Result::Ok(updated)
+    }
 
-impl Database {
-    pub fn new(client: tokio_postgres::Client) -> Self {
-        Self {
-            client: Arc::new(client),
+    /// Times the game out if its clock, as rebuilt from the log, ran past the limit.
+    async fn expire(&self, game_id: uuid::Uuid) -> Result<bool, Error> {
+        let mut g = self.get(game_id).await?;
+        if !g.check_timeout(time::OffsetDateTime::now_utc()) {
+            return Result::Ok(false);
         }
+        self.update(g).await?;
+        Result::Ok(true)
     }
+
+    /// Tells every listening instance that the game changed.
+    async fn notify(client: &impl GenericClient, game_id: uuid::Uuid) -> Result<(), Error> {
+        client
+            .execute(
+                "SELECT pg_notify($1, $2)",
+                &[&GAME_UPDATES, &game_id.to_string()],
+            )
+            .await?;
+        Result::Ok(())
+    }
+
+    async fn append_events(
+        client: &impl GenericClient,
+        game_id: uuid::Uuid,
+        events: Vec<Logged>,
+    ) -> Result<(), Error> {
+        if events.is_empty() {
+            return Result::Ok(());
+        }
+        let seqs: Vec<i32> = events.iter().map(|e| e.seq).collect();
+        let kinds: Vec<i32> = events.iter().map(|e| e.kind).collect();
+        let cell_rows: Vec<Option<i32>> = events.iter().map(|e| e.cell_row).collect();
+        let cell_cols: Vec<Option<i32>> = events.iter().map(|e| e.cell_col).collect();
+        let created: Vec<time::OffsetDateTime> = events.iter().map(|e| e.created).collect();
+        client
+            .execute(
+                r##"
+                INSERT INTO game_events
+                    (game_id, seq, kind, cell_row, cell_col, created)
+                SELECT
+                    $1, *
+                FROM
+                    UNNEST($2::INT[], $3::INT[], $4::INT[], $5::INT[], $6::TIMESTAMPTZ[])
+            "##,
+                &[&game_id, &seqs, &kinds, &cell_rows, &cell_cols, &created],
+            )
+            .await
+            .map_err(|e| match e.code() {
+                Some(&SqlState::UNIQUE_VIOLATION) => Error::WriteConflict {
+                    id: game_id.to_string(),
+                },
+                _ => e.into(),
+            })?;
+        Result::Ok(())
+    }
+
     pub async fn run_migrations(client: &mut tokio_postgres::Client) -> Result<(), Error> {
         embedded::migrations::runner().run_async(client).await?;
         Result::Ok(())
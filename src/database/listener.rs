@@ -0,0 +1,56 @@
+use crate::service::Error;
+use futures_util::{stream, StreamExt};
+use tokio::sync::mpsc;
+use tokio_postgres::AsyncMessage;
+
+/// Channel notified with the game id after every insert and update.
+pub const GAME_UPDATES: &str = "game_updates";
+
+/// Ids of the games changed by any instance, read over a dedicated connection that
+/// listens on `GAME_UPDATES`.
+pub struct Listener {
+    _client: tokio_postgres::Client,
+    ids: mpsc::UnboundedReceiver<uuid::Uuid>,
+}
+
+impl Listener {
+    pub async fn connect(database_url: &str) -> Result<Self, Error> {
+        let (client, mut conn) =
+            tokio_postgres::connect(database_url, tokio_postgres::NoTls).await?;
+        let (sender, ids) = mpsc::unbounded_channel();
+        let mut messages = stream::poll_fn(move |cx| conn.poll_message(cx));
+        tokio::spawn(async move {
+            while let Some(message) = messages.next().await {
+                match message {
+                    Ok(AsyncMessage::Notification(n)) if n.channel() == GAME_UPDATES => {
+                        match n.payload().parse() {
+                            Ok(id) => {
+                                if sender.send(id).is_err() {
+                                    break;
+                                }
+                            }
+                            Err(_) => tracing::warn!("bad game update payload: {}", n.payload()),
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        tracing::error!("listener connection error: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+        client
+            .batch_execute(&format!("LISTEN {}", GAME_UPDATES))
+            .await?;
+        Ok(Self {
+            _client: client,
+            ids,
+        })
+    }
+
+    /// Waits for the next changed game, `None` once the connection is gone.
+    pub async fn recv(&mut self) -> Option<uuid::Uuid> {
+        self.ids.recv().await
+    }
+}
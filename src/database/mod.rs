@@ -1,4 +1,8 @@
+#[allow(clippy::module_inception)]
 mod database;
 pub use self::database::Database;
 
+mod listener;
+pub use self::listener::{Listener, GAME_UPDATES};
+
 mod model;
@@ -1,4 +1,6 @@
-use crate::service::State;
+use crate::service::{
+    self, Cell, CellState, Daily, Difficulty, Event, Mark, Move, SafeArea, State, Topology,
+};
 use serde::{Deserialize, Serialize};
 use tokio_postgres::types::{FromSql, Json, ToSql};
 
@@ -8,18 +10,157 @@ pub(super) struct Game {
     pub created: time::OffsetDateTime,
     pub updated: time::OffsetDateTime,
     pub title: String,
-    pub board: Json<Board>,
-    pub player_board: Json<Board>,
+    pub board: Option<Json<Board>>,
+    pub player_board: Option<Json<Board>>,
+    pub rows: i32,
+    pub cols: i32,
+    pub board_bits: Option<Vec<u8>>,
+    pub player_bits: Option<Vec<u8>>,
+    pub mines: i32,
+    pub safe_area: i32,
+    pub no_guess: bool,
+    pub topology: i32,
+    pub wrap: bool,
+    pub seed: i64,
+    pub rng_version: i32,
     pub state: i32,
     pub duration_seconds: i32,
     pub elapsed_seconds: i32,
     pub score: i32,
     pub resumed_timestamp: Option<time::OffsetDateTime>,
+    pub bbbv: i32,
+    pub reveal_clicks: i32,
+    pub chord_clicks: i32,
+    pub flag_clicks: i32,
+    pub question_clicks: i32,
+    pub hints: i32,
+    pub practice: bool,
+    pub undos: i32,
+    pub version: i32,
+    pub daily_date: Option<time::Date>,
+    pub daily_difficulty: Option<i32>,
+    pub player: Option<String>,
+    pub race_id: Option<uuid::Uuid>,
+    pub history: Json<Vec<Played>>,
+    pub finished: Option<time::OffsetDateTime>,
 }
 
+/// Move of the history kept in the `history` column, in the order played.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct Played {
+    pub kind: i32,
+    pub cell_row: i32,
+    pub cell_col: i32,
+    #[serde(with = "time::serde::timestamp::microseconds")]
+    pub created: time::OffsetDateTime,
+    pub state: i32,
+    pub undone: bool,
+}
+
+/// Row of the `game_events` table.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Logged {
+    pub seq: i32,
+    pub kind: i32,
+    pub cell_row: Option<i32>,
+    pub cell_col: Option<i32>,
+    pub created: time::OffsetDateTime,
+}
+
+/// Board column as stored in JSONB before boards were bit-packed, still read for rows that
+/// have not been converted. `Legacy` boards predate typed cells and keep the original
+/// character codes: 'x' and '0'-'8' for layouts, 'h', 'v', '!' and '?' for players.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub(super) struct Board {
-    pub rows: Vec<Vec<char>>,
+#[serde(untagged)]
+pub(super) enum Board {
+    Typed { columns: i32, cells: Vec<i32> },
+    Legacy { rows: Vec<Vec<char>> },
+}
+
+pub(super) fn i32_to_cell(cell: i32) -> Cell {
+    match cell {
+        -1 => Cell::Mine,
+        0..=8 => Cell::Empty(cell as u8),
+        _ => panic!("Invalid cell"),
+    }
+}
+
+pub(super) fn char_to_cell(cell: char) -> Cell {
+    match cell {
+        'x' => Cell::Mine,
+        '0'..='8' => Cell::Empty(cell as u8 - b'0'),
+        _ => panic!("Invalid cell"),
+    }
+}
+
+pub(super) fn cell_state_to_i32(state: CellState) -> i32 {
+    match state {
+        CellState::Hidden => 0,
+        CellState::Revealed => 1,
+        CellState::Marked(Mark::Flag) => 2,
+        CellState::Marked(Mark::Question) => 3,
+    }
+}
+
+pub(super) fn i32_to_cell_state(state: i32) -> CellState {
+    match state {
+        0 => CellState::Hidden,
+        1 => CellState::Revealed,
+        2 => CellState::Marked(Mark::Flag),
+        3 => CellState::Marked(Mark::Question),
+        _ => panic!("Invalid cell state"),
+    }
+}
+
+pub(super) fn char_to_cell_state(state: char) -> CellState {
+    match state {
+        'h' => CellState::Hidden,
+        'v' => CellState::Revealed,
+        '!' => CellState::Marked(Mark::Flag),
+        '?' => CellState::Marked(Mark::Question),
+        _ => panic!("Invalid cell state"),
+    }
+}
+
+/// Packs `bits`-wide codes into bytes, first code in the least significant bits.
+fn pack(codes: impl Iterator<Item = i32>, bits: usize) -> Vec<u8> {
+    let per_byte = 8 / bits;
+    let mut packed = vec![];
+    for (i, code) in codes.enumerate() {
+        if i % per_byte == 0 {
+            packed.push(0);
+        }
+        *packed.last_mut().unwrap() |= (code as u8) << ((i % per_byte) * bits);
+    }
+    packed
+}
+
+fn unpack(packed: &[u8], bits: usize, count: usize) -> Vec<i32> {
+    let per_byte = 8 / bits;
+    let mask = (1u8 << bits) - 1;
+    (0..count)
+        .map(|i| ((packed[i / per_byte] >> ((i % per_byte) * bits)) & mask) as i32)
+        .collect()
+}
+
+fn board_from_model<T: Copy>(
+    board: Board,
+    from_i32: fn(i32) -> T,
+    from_char: fn(char) -> T,
+) -> Option<service::Board<T>> {
+    match board {
+        Board::Typed { columns, cells } if columns > 0 && !cells.is_empty() => {
+            let rows = cells.len() as i32 / columns;
+            let cells = cells.into_iter().map(from_i32).collect();
+            Some(service::Board::from_cells(rows, columns, cells))
+        }
+        Board::Legacy { rows } if !rows.is_empty() => {
+            let (r, c) = (rows.len() as i32, rows[0].len() as i32);
+            let cells = rows.into_iter().flatten().map(from_char).collect();
+            Some(service::Board::from_cells(r, c, cells))
+        }
+        _ => None,
+    }
 }
 
 pub(super) fn state_to_i32(state: State) -> i32 {
@@ -45,36 +186,357 @@ pub(super) fn i32_to_state(state: i32) -> State {
     }
 }
 
+pub(super) fn safe_area_to_i32(safe_area: SafeArea) -> i32 {
+    match safe_area {
+        SafeArea::Cell => 0,
+        SafeArea::Opening => 1,
+    }
+}
+
+pub(super) fn i32_to_safe_area(safe_area: i32) -> SafeArea {
+    match safe_area {
+        0 => SafeArea::Cell,
+        1 => SafeArea::Opening,
+        _ => panic!("Invalid safe area"),
+    }
+}
+
+pub(super) fn topology_to_i32(topology: Topology) -> i32 {
+    match topology {
+        Topology::Square => 0,
+        Topology::Hex => 1,
+    }
+}
+
+pub(super) fn i32_to_topology(topology: i32) -> Topology {
+    match topology {
+        0 => Topology::Square,
+        1 => Topology::Hex,
+        _ => panic!("Invalid topology"),
+    }
+}
+
+pub(super) fn difficulty_to_i32(difficulty: Difficulty) -> i32 {
+    match difficulty {
+        Difficulty::Beginner => 0,
+        Difficulty::Intermediate => 1,
+        Difficulty::Expert => 2,
+    }
+}
+
+pub(super) fn i32_to_difficulty(difficulty: i32) -> Difficulty {
+    match difficulty {
+        0 => Difficulty::Beginner,
+        1 => Difficulty::Intermediate,
+        2 => Difficulty::Expert,
+        _ => panic!("Invalid difficulty"),
+    }
+}
+
 pub(super) fn map_to_model(game: crate::service::Game) -> Game {
+    let history = Json(map_to_history(&game));
     Game {
         id: game.id,
         created: game.created,
         updated: game.updated,
         title: game.title,
-        board: Json(Board { rows: game.board }),
-        player_board: Json(Board {
-            rows: game.player_board,
-        }),
+        board: None,
+        player_board: None,
+        rows: game.player_board.rows(),
+        cols: game.player_board.cols(),
+        board_bits: game
+            .board
+            .as_ref()
+            .map(|board| pack(board.cells().iter().map(|c| (*c == Cell::Mine) as i32), 1)),
+        player_bits: Some(pack(
+            game.player_board
+                .cells()
+                .iter()
+                .map(|c| cell_state_to_i32(*c)),
+            2,
+        )),
+        mines: game.mines,
+        safe_area: safe_area_to_i32(game.safe_area),
+        no_guess: game.no_guess,
+        topology: topology_to_i32(game.topology),
+        wrap: game.wrap,
+        seed: game.seed as i64,
+        rng_version: game.rng_version,
         state: state_to_i32(game.state),
         duration_seconds: game.duration_seconds,
         elapsed_seconds: game.elapsed_seconds,
         score: game.score,
         resumed_timestamp: game.resumed_timestamp,
+        bbbv: game.bbbv,
+        reveal_clicks: game.clicks.reveal,
+        chord_clicks: game.clicks.chord,
+        flag_clicks: game.clicks.flag,
+        question_clicks: game.clicks.question,
+        hints: game.hints,
+        practice: game.practice,
+        undos: game.undos,
+        version: game.version,
+        daily_date: game.daily.as_ref().map(|d| d.date),
+        daily_difficulty: game.daily.as_ref().map(|d| difficulty_to_i32(d.difficulty)),
+        player: game.daily.map(|d| d.player),
+        race_id: game.race,
+        history,
+        finished: game.finished,
+    }
+}
+
+fn move_to_model(action: Move) -> (i32, (i32, i32)) {
+    match action {
+        Move::Reveal(p) => (0, p.into()),
+        Move::Chord(p) => (1, p.into()),
+        Move::Flag(p) => (2, p.into()),
+        Move::Question(p) => (3, p.into()),
+        _ => panic!("Invalid move"),
+    }
+}
+
+fn move_from_model(game: &service::Game, played: &Played) -> Move {
+    let p = game
+        .new_point((played.cell_row, played.cell_col))
+        .expect("Invalid move point");
+    match played.kind {
+        0 => Move::Reveal(p),
+        1 => Move::Chord(p),
+        2 => Move::Flag(p),
+        3 => Move::Question(p),
+        _ => panic!("Invalid move"),
+    }
+}
+
+fn map_to_history(game: &service::Game) -> Vec<Played> {
+    game.history
+        .iter()
+        .map(|played| {
+            let (kind, (cell_row, cell_col)) = move_to_model(played.action);
+            Played {
+                kind,
+                cell_row,
+                cell_col,
+                created: played.created,
+                state: state_to_i32(played.state),
+                undone: played.undone,
+            }
+        })
+        .collect()
+}
+
+fn event_to_model(event: Event) -> (i32, Option<(i32, i32)>) {
+    match event {
+        Event::GameCreated => (0, None),
+        Event::CellRevealed(p) => (1, Some(p.into())),
+        Event::Chorded(p) => (2, Some(p.into())),
+        Event::Flagged(p) => (3, Some(p.into())),
+        Event::Questioned(p) => (4, Some(p.into())),
+        Event::Paused => (5, None),
+        Event::Resumed => (6, None),
+        Event::Undone => (7, None),
+        Event::Redone => (8, None),
+        Event::HintTaken => (9, None),
+        Event::TimedOut => (10, None),
+        Event::Prerevealed(p) => (11, Some(p.into())),
+    }
+}
+
+fn event_from_model(game: &service::Game, logged: &Logged) -> Event {
+    let p = || {
+        game.new_point((logged.cell_row.unwrap_or(-1), logged.cell_col.unwrap_or(-1)))
+            .expect("Invalid event point")
+    };
+    match logged.kind {
+        0 => Event::GameCreated,
+        1 => Event::CellRevealed(p()),
+        2 => Event::Chorded(p()),
+        3 => Event::Flagged(p()),
+        4 => Event::Questioned(p()),
+        5 => Event::Paused,
+        6 => Event::Resumed,
+        7 => Event::Undone,
+        8 => Event::Redone,
+        9 => Event::HintTaken,
+        10 => Event::TimedOut,
+        11 => Event::Prerevealed(p()),
+        _ => panic!("Invalid event"),
     }
 }
 
+/// Rows of the `game_events` table, numbered from `first_seq`.
+pub(super) fn map_to_events(events: &[service::Logged], first_seq: i32) -> Vec<Logged> {
+    events
+        .iter()
+        .enumerate()
+        .map(|(i, logged)| {
+            let (kind, cell) = event_to_model(logged.event);
+            Logged {
+                seq: first_seq + i as i32,
+                kind,
+                cell_row: cell.map(|c| c.0),
+                cell_col: cell.map(|c| c.1),
+                created: logged.created,
+            }
+        })
+        .collect()
+}
+
+pub(super) fn map_from_events(game: &service::Game, events: &[Logged]) -> Vec<service::Logged> {
+    events
+        .iter()
+        .map(|logged| service::Logged {
+            event: event_from_model(game, logged),
+            created: logged.created,
+        })
+        .collect()
+}
+
 pub(super) fn map_from_model(game: Game) -> crate::service::Game {
-    crate::service::Game {
+    let count = (game.rows * game.cols) as usize;
+    let player_board = match (&game.player_bits, game.player_board) {
+        (Some(bits), _) => {
+            let cells = unpack(bits, 2, count).into_iter().map(i32_to_cell_state);
+            service::Board::from_cells(game.rows, game.cols, cells.collect())
+        }
+        (None, Some(json)) => board_from_model(json.0, i32_to_cell_state, char_to_cell_state)
+            .expect("empty player board"),
+        (None, None) => panic!("missing player board"),
+    };
+    let mut g = crate::service::Game {
         id: game.id,
         created: game.created,
         updated: game.updated,
         title: game.title,
-        board: game.board.0.rows,
-        player_board: game.player_board.0.rows,
+        board: None,
+        player_board,
+        mines: game.mines,
+        safe_remaining: 0,
+        safe_area: i32_to_safe_area(game.safe_area),
+        no_guess: game.no_guess,
+        topology: i32_to_topology(game.topology),
+        wrap: game.wrap,
+        seed: game.seed as u64,
+        rng_version: game.rng_version,
         state: i32_to_state(game.state),
         duration_seconds: game.duration_seconds,
         elapsed_seconds: game.elapsed_seconds,
         score: game.score,
         resumed_timestamp: game.resumed_timestamp,
+        finished: game.finished,
+        bbbv: game.bbbv,
+        clicks: service::Clicks {
+            reveal: game.reveal_clicks,
+            chord: game.chord_clicks,
+            flag: game.flag_clicks,
+            question: game.question_clicks,
+        },
+        hints: game.hints,
+        practice: game.practice,
+        undos: game.undos,
+        history: vec![],
+        version: game.version,
+        events: vec![],
+        daily: match (game.daily_date, game.daily_difficulty, game.player) {
+            (Some(date), Some(difficulty), Some(player)) => Some(Daily {
+                date,
+                difficulty: i32_to_difficulty(difficulty),
+                player,
+            }),
+            _ => None,
+        },
+        race: game.race_id,
+        layout_secret: false,
+    };
+    match (&game.board_bits, game.board) {
+        (Some(bits), _) => {
+            let mines: Vec<bool> = unpack(bits, 1, count).iter().map(|m| *m == 1).collect();
+            g.set_mines(&mines);
+        }
+        (None, Some(json)) => g.board = board_from_model(json.0, i32_to_cell, char_to_cell),
+        (None, None) => {}
+    }
+    g.safe_remaining = g.count_safe_remaining();
+    g.history = game
+        .history
+        .0
+        .iter()
+        .map(|played| service::Played {
+            action: move_from_model(&g, played),
+            created: played.created,
+            state: i32_to_state(played.state),
+            undone: played.undone,
+        })
+        .collect();
+    // Games generated before scoring have no stored 3BV.
+    if g.bbbv == 0 && g.is_generated() {
+        g.bbbv = g.count_bbbv();
+    }
+    g
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_json_when_board_from_model_then_reads_chars() {
+        let layout: Board = serde_json::from_str(r#"{"rows": [["x", "1"], ["1", "1"]]}"#).unwrap();
+        let player: Board = serde_json::from_str(r#"{"rows": [["!", "v"], ["h", "?"]]}"#).unwrap();
+        let layout = board_from_model(layout, i32_to_cell, char_to_cell).unwrap();
+        let player = board_from_model(player, i32_to_cell_state, char_to_cell_state).unwrap();
+        assert_eq!(
+            layout.cells(),
+            [Cell::Mine, Cell::Empty(1), Cell::Empty(1), Cell::Empty(1)]
+        );
+        assert_eq!(
+            player.cells(),
+            [
+                CellState::Marked(Mark::Flag),
+                CellState::Revealed,
+                CellState::Hidden,
+                CellState::Marked(Mark::Question)
+            ]
+        );
+    }
+
+    #[test]
+    fn typed_json_when_board_from_model_then_reads_codes() {
+        let json = r#"{"columns":3,"cells":[-1,2,0]}"#;
+        let read: Board = serde_json::from_str(json).unwrap();
+        let cells = vec![Cell::Mine, Cell::Empty(2), Cell::Empty(0)];
+        assert_eq!(
+            board_from_model(read, i32_to_cell, char_to_cell),
+            Some(service::Board::from_cells(1, 3, cells))
+        );
+    }
+
+    #[test]
+    fn generated_game_when_round_trip_then_same_boards() {
+        let mut g = crate::service::Game::new(3, 5, 4);
+        g.seed = 1;
+        let p = g.new_point((1, 2)).unwrap();
+        let now = time::OffsetDateTime::now_utc();
+        g.generate_at(p).unwrap();
+        g.apply(Move::Reveal(p), now).unwrap();
+        g.apply(Move::Flag(g.new_point((0, 4)).unwrap()), now)
+            .unwrap();
+        let model = map_to_model(g.clone());
+        assert_eq!(model.board_bits.as_ref().unwrap().len(), 2);
+        assert_eq!(model.player_bits.as_ref().unwrap().len(), 4);
+        let read = map_from_model(model);
+        assert_eq!(read.board, g.board);
+        assert_eq!(read.player_board, g.player_board);
+        assert_eq!(read.safe_remaining, g.safe_remaining);
+        assert_eq!(read.bbbv, g.bbbv);
+        assert_eq!(read.history, g.history);
+    }
+
+    #[test]
+    fn ungenerated_board_when_board_from_model_then_none() {
+        let legacy: Board = serde_json::from_str(r#"{"rows": []}"#).unwrap();
+        let typed: Board = serde_json::from_str(r#"{"columns": 0, "cells": []}"#).unwrap();
+        assert!(board_from_model(legacy, i32_to_cell, char_to_cell).is_none());
+        assert!(board_from_model(typed, i32_to_cell, char_to_cell).is_none());
     }
 }
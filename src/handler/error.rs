@@ -0,0 +1,184 @@
+use crate::handler::model::At;
+use crate::service::Error;
+use axum::extract::rejection::JsonRejection;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+/// Body of every failed request. `code` is stable and meant for clients to match on,
+/// `message` is for people and may change.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
+    #[serde(default)]
+    pub details: Details,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Details {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub point: Option<At>,
+}
+
+/// Error returned by the handlers, rendered as a status code and an `ErrorBody`.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    body: ErrorBody,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, code: &str, message: String) -> Self {
+        Self {
+            status,
+            body: ErrorBody {
+                code: code.to_string(),
+                message,
+                details: Details::default(),
+            },
+        }
+    }
+
+    fn with_id(mut self, id: &str) -> Self {
+        self.body.details.id = Some(id.to_string());
+        self
+    }
+
+    fn with_point(mut self, point: (i32, i32)) -> Self {
+        self.body.details.point = Some(At {
+            x: point.1,
+            y: point.0,
+        });
+        self
+    }
+
+    pub(super) fn invalid_id(id: &str) -> Self {
+        Self::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_id",
+            format!("invalid game id {id:?}"),
+        )
+        .with_id(id)
+    }
+
+    pub(super) fn invalid_message(message: String) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "invalid_message", message)
+    }
+
+    pub(super) fn into_body(self) -> ErrorBody {
+        self.body
+    }
+
+    pub(super) fn invalid_parameters(message: &str) -> Self {
+        Self::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_parameters",
+            message.to_string(),
+        )
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(e: Error) -> Self {
+        let message = e.to_string();
+        match e {
+            Error::NotFound { id } => {
+                Self::new(StatusCode::NOT_FOUND, "not_found", message).with_id(&id)
+            }
+            Error::InvalidPoint { point } => {
+                Self::new(StatusCode::BAD_REQUEST, "invalid_point", message).with_point(point)
+            }
+            Error::GamePaused { id } => {
+                Self::new(StatusCode::CONFLICT, "game_paused", message).with_id(&id)
+            }
+            Error::WriteConflict { id } => {
+                Self::new(StatusCode::CONFLICT, "write_conflict", message).with_id(&id)
+            }
+            Error::GameOver { id } => {
+                Self::new(StatusCode::GONE, "game_over", message).with_id(&id)
+            }
+            Error::CellAlreadyRevealed { point } => Self::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "cell_already_revealed",
+                message,
+            )
+            .with_point(point),
+            Error::CellFlagged { point } => {
+                Self::new(StatusCode::UNPROCESSABLE_ENTITY, "cell_flagged", message)
+                    .with_point(point)
+            }
+            Error::NoHint { id } => {
+                Self::new(StatusCode::NOT_FOUND, "no_hint", message).with_id(&id)
+            }
+            Error::UndoDisabled { id } => {
+                Self::new(StatusCode::FORBIDDEN, "undo_disabled", message).with_id(&id)
+            }
+            Error::NothingToUndo { id } => {
+                Self::new(StatusCode::CONFLICT, "nothing_to_undo", message).with_id(&id)
+            }
+            Error::NothingToRedo { id } => {
+                Self::new(StatusCode::CONFLICT, "nothing_to_redo", message).with_id(&id)
+            }
+            Error::DailyAlreadyPlayed { .. } => {
+                Self::new(StatusCode::CONFLICT, "daily_already_played", message)
+            }
+            Error::ReservedSeed { .. } => {
+                Self::new(StatusCode::UNPROCESSABLE_ENTITY, "reserved_seed", message)
+            }
+            Error::InvalidLayout { .. } => {
+                Self::new(StatusCode::BAD_REQUEST, "invalid_layout", message)
+            }
+            Error::NoGuessUnavailable { id } => Self::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "no_guess_unavailable",
+                message,
+            )
+            .with_id(&id),
+            Error::ProbabilitiesDisabled { id } => {
+                Self::new(StatusCode::FORBIDDEN, "probabilities_disabled", message).with_id(&id)
+            }
+            Error::ProbabilitiesUnavailable { id } => Self::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "probabilities_unavailable",
+                message,
+            )
+            .with_id(&id),
+            Error::NoSuchMove { id, .. } => {
+                Self::new(StatusCode::NOT_FOUND, "no_such_move", message).with_id(&id)
+            }
+            Error::RaceFull { id } => {
+                Self::new(StatusCode::CONFLICT, "race_full", message).with_id(&id)
+            }
+            Error::RaceFinished { id } => {
+                Self::new(StatusCode::GONE, "race_finished", message).with_id(&id)
+            }
+            Error::AssistanceDisabled { id } => {
+                Self::new(StatusCode::FORBIDDEN, "assistance_disabled", message).with_id(&id)
+            }
+            Error::PlayerAlreadyJoined { id, .. } => {
+                Self::new(StatusCode::CONFLICT, "player_already_joined", message).with_id(&id)
+            }
+            Error::Database(_) | Error::Pool(_) | Error::Refinery(_) => Self::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal",
+                "internal error".to_string(),
+            ),
+        }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::new(rejection.status(), "invalid_body", rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.body)).into_response()
+    }
+}
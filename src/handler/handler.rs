@@ -1,15 +1,32 @@
+use crate::handler::error::ApiError;
+use crate::handler::model::map_to_daily_results;
+use crate::handler::model::map_to_difficulty;
+use crate::handler::model::map_to_hint;
+use crate::handler::model::map_to_import_options;
 use crate::handler::model::map_to_model;
-use crate::handler::model::Game;
+use crate::handler::model::map_to_options;
+use crate::handler::model::map_to_probabilities;
+use crate::handler::model::map_to_race;
+use crate::handler::model::map_to_replay;
+use crate::handler::model::map_to_replay_frame;
+use crate::handler::model::map_to_standings;
+use crate::handler::model::{
+    At, Create, DailyResults, Difficulty, Game, Hint, Import, JoinRace, NewDaily, NewRace,
+    Probabilities, Race, Replay, ReplayFrame, Standings, Topology,
+};
+use crate::handler::socket;
+use crate::handler::spectate;
 use crate::service::Error;
-use crate::service::ServiceTrait;
+use crate::service::{Broadcast, Options, ServiceTrait};
 use axum::extract;
-use axum::http::StatusCode;
+use axum::extract::rejection::JsonRejection;
 use axum::routing::post;
 use axum::{async_trait, Json};
 use axum::{extract::Path, extract::State, routing::get, Router};
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::Arc;
+use tokio::sync::broadcast;
 
 #[cfg(test)]
 use mockall::automock;
@@ -17,7 +34,21 @@ use mockall::automock;
 #[cfg_attr(test, automock)]
 #[async_trait]
 pub trait HandlerTrait {
-    async fn new_game(&self, rows: i32, cols: i32, mines: i32) -> Result<Game, Error>;
+    async fn new_game(
+        &self,
+        rows: i32,
+        cols: i32,
+        mines: i32,
+        options: Options,
+    ) -> Result<Game, Error>;
+    async fn import_game(
+        &self,
+        layout: String,
+        title: String,
+        options: Options,
+    ) -> Result<Game, Error>;
+    async fn daily_game(&self, player: String, difficulty: Difficulty) -> Result<Game, Error>;
+    async fn daily_results(&self, date: time::Date) -> Result<DailyResults, Error>;
     async fn get_game(&self, game_id: uuid::Uuid) -> Result<Game, Error>;
     async fn pause_game(&self, game_id: uuid::Uuid) -> Result<Game, Error>;
     async fn resume_game(&self, game_id: uuid::Uuid) -> Result<Game, Error>;
@@ -25,11 +56,48 @@ pub trait HandlerTrait {
     async fn mark_as_question(&self, game_id: uuid::Uuid, point: (i32, i32))
         -> Result<Game, Error>;
     async fn reveal(&self, game_id: uuid::Uuid, point: (i32, i32)) -> Result<Game, Error>;
+    async fn chord(&self, game_id: uuid::Uuid, point: (i32, i32)) -> Result<Game, Error>;
+    async fn hint(&self, game_id: uuid::Uuid) -> Result<Hint, Error>;
+    async fn probabilities(&self, game_id: uuid::Uuid) -> Result<Probabilities, Error>;
+    async fn undo_game(&self, game_id: uuid::Uuid) -> Result<Game, Error>;
+    async fn redo_game(&self, game_id: uuid::Uuid) -> Result<Game, Error>;
+    async fn replay(&self, game_id: uuid::Uuid) -> Result<Replay, Error>;
+    async fn replay_at(&self, game_id: uuid::Uuid, n: usize) -> Result<ReplayFrame, Error>;
+    async fn new_race(&self, params: NewRace) -> Result<Race, Error>;
+    async fn get_race(&self, race_id: uuid::Uuid) -> Result<Race, Error>;
+    async fn join_race(&self, race_id: uuid::Uuid, player: String) -> Result<Game, Error>;
+    async fn race_standings(&self, race_id: uuid::Uuid) -> Result<Standings, Error>;
 }
 
+/// Longest player name accepted for daily challenges and races.
+const MAX_PLAYER_LENGTH: usize = 64;
+/// Longest title of an imported game, as wide as the `title` column.
+const MAX_TITLE_LENGTH: usize = 255;
+/// Most player slots a race can have.
+const MAX_RACE_PLAYERS: i32 = 16;
+/// Most cells of a no-guess board, as each failed layout costs a full solver run.
+const MAX_NO_GUESS_CELLS: i64 = 50 * 50;
+
 #[derive(Debug, Clone)]
 pub struct Handler {
     service: Arc<dyn ServiceTrait + Send + Sync>,
+    limits: Limits,
+}
+
+/// Largest board accepted when creating a game.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_rows: i32,
+    pub max_columns: i32,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_rows: 1000,
+            max_columns: 1000,
+        }
+    }
 }
 
 impl Debug for dyn ServiceTrait + Send + Sync {
@@ -40,11 +108,40 @@ impl Debug for dyn ServiceTrait + Send + Sync {
 
 #[async_trait]
 impl HandlerTrait for Handler {
-    async fn new_game(&self, rows: i32, cols: i32, mines: i32) -> Result<Game, Error> {
-        let g = self.service.new_game(rows, cols, mines).await?;
+    async fn new_game(
+        &self,
+        rows: i32,
+        cols: i32,
+        mines: i32,
+        options: Options,
+    ) -> Result<Game, Error> {
+        let g = self.service.new_game(rows, cols, mines, options).await?;
+        Ok(map_to_model(g))
+    }
+
+    async fn import_game(
+        &self,
+        layout: String,
+        title: String,
+        options: Options,
+    ) -> Result<Game, Error> {
+        let g = self.service.import_game(layout, title, options).await?;
         Ok(map_to_model(g))
     }
 
+    async fn daily_game(&self, player: String, difficulty: Difficulty) -> Result<Game, Error> {
+        let g = self
+            .service
+            .daily_game(player, map_to_difficulty(difficulty))
+            .await?;
+        Ok(map_to_model(g))
+    }
+
+    async fn daily_results(&self, date: time::Date) -> Result<DailyResults, Error> {
+        let results = self.service.daily_results(date).await?;
+        Ok(map_to_daily_results(date, results))
+    }
+
     async fn get_game(&self, game_id: uuid::Uuid) -> Result<Game, Error> {
         let g = self.service.get_game(game_id).await?;
         Ok(map_to_model(g))
@@ -78,6 +175,64 @@ impl HandlerTrait for Handler {
         let g = self.service.reveal(game_id, point).await?;
         Ok(map_to_model(g))
     }
+
+    async fn chord(&self, game_id: uuid::Uuid, point: (i32, i32)) -> Result<Game, Error> {
+        let g = self.service.chord(game_id, point).await?;
+        Ok(map_to_model(g))
+    }
+
+    async fn hint(&self, game_id: uuid::Uuid) -> Result<Hint, Error> {
+        let hint = self.service.hint(game_id).await?;
+        Ok(map_to_hint(hint))
+    }
+
+    async fn probabilities(&self, game_id: uuid::Uuid) -> Result<Probabilities, Error> {
+        let p = self.service.probabilities(game_id).await?;
+        Ok(map_to_probabilities(p))
+    }
+
+    async fn undo_game(&self, game_id: uuid::Uuid) -> Result<Game, Error> {
+        let g = self.service.undo_game(game_id).await?;
+        Ok(map_to_model(g))
+    }
+
+    async fn redo_game(&self, game_id: uuid::Uuid) -> Result<Game, Error> {
+        let g = self.service.redo_game(game_id).await?;
+        Ok(map_to_model(g))
+    }
+
+    async fn replay(&self, game_id: uuid::Uuid) -> Result<Replay, Error> {
+        let replay = self.service.replay(game_id).await?;
+        Ok(map_to_replay(replay))
+    }
+
+    async fn replay_at(&self, game_id: uuid::Uuid, n: usize) -> Result<ReplayFrame, Error> {
+        let replay = self.service.replay(game_id).await?;
+        Ok(map_to_replay_frame(n, replay.at(n)?))
+    }
+
+    async fn new_race(&self, params: NewRace) -> Result<Race, Error> {
+        let race = self
+            .service
+            .new_race(params.rows, params.columns, params.mines, params.players)
+            .await?;
+        Ok(map_to_race(race))
+    }
+
+    async fn get_race(&self, race_id: uuid::Uuid) -> Result<Race, Error> {
+        let race = self.service.get_race(race_id).await?;
+        Ok(map_to_race(race))
+    }
+
+    async fn join_race(&self, race_id: uuid::Uuid, player: String) -> Result<Game, Error> {
+        let g = self.service.join_race(race_id, player).await?;
+        Ok(map_to_model(g))
+    }
+
+    async fn race_standings(&self, race_id: uuid::Uuid) -> Result<Standings, Error> {
+        let (race, standings) = self.service.race_standings(race_id).await?;
+        Ok(map_to_standings(race, standings))
+    }
 }
 
 impl Handler {
@@ -87,16 +242,49 @@ impl Handler {
     {
         Self {
             service: Arc::new(service),
+            limits: Limits::default(),
         }
     }
 
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub(super) async fn subscribe(
+        &self,
+        game_id: uuid::Uuid,
+    ) -> Result<broadcast::Receiver<Broadcast>, Error> {
+        self.service.subscribe(game_id).await
+    }
+
+    #[allow(clippy::let_and_return)]
     pub fn router(&self) -> Router {
         let router = Router::new()
             .route("/api/v1/games/:id/state/", get(state_handler))
             .route("/api/v1/games/new/", post(new_handler))
+            .route("/api/v1/games/import/", post(import_handler))
+            .route("/api/v1/games/daily/", post(daily_handler))
+            .route("/api/v1/daily/:date/results/", get(daily_results_handler))
+            .route("/api/v1/races/", post(new_race_handler))
+            .route("/api/v1/races/:id/", get(race_handler))
+            .route("/api/v1/races/:id/join/", post(join_race_handler))
+            .route("/api/v1/races/:id/standings/", get(race_standings_handler))
             .route("/api/v1/games/:id/pause/", post(pause_handler))
             .route("/api/v1/games/:id/resume/", post(resume_handler))
             .route("/api/v1/games/:id/reveal/", post(reveal_handler))
+            .route("/api/v1/games/:id/chord/", post(chord_handler))
+            .route("/api/v1/games/:id/undo/", post(undo_handler))
+            .route("/api/v1/games/:id/redo/", post(redo_handler))
+            .route("/api/v1/games/:id/hint/", get(hint_handler))
+            .route("/api/v1/games/:id/ws", get(socket::ws_handler))
+            .route("/api/v1/games/:id/events/", get(spectate::events_handler))
+            .route("/api/v1/games/:id/replay/", get(replay_handler))
+            .route("/api/v1/games/:id/replay/at/:n/", get(replay_at_handler))
+            .route(
+                "/api/v1/games/:id/probabilities/",
+                get(probabilities_handler),
+            )
             .route(
                 "/api/v1/games/:id/mark_as_flag/",
                 post(mark_as_flag_handler),
@@ -113,128 +301,299 @@ impl Handler {
 async fn state_handler(
     handler: State<Handler>,
     Path(params): Path<HashMap<String, String>>,
-) -> (StatusCode, Json<Game>) {
-    match get_id(params) {
-        Some(id) => match handler.get_game(id).await {
-            Ok(g) => (StatusCode::OK, Json(g)),
-            Err(Error::NotFound { id: _ }) => (StatusCode::NOT_FOUND, Json(Game::default())),
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(Game::default())),
-        },
-        None => (StatusCode::BAD_REQUEST, Json(Game::default())),
-    }
+) -> Result<Json<Game>, ApiError> {
+    let id = get_id(&params)?;
+    Ok(Json(handler.get_game(id).await?))
 }
 
 async fn pause_handler(
     handler: State<Handler>,
     Path(params): Path<HashMap<String, String>>,
-) -> (StatusCode, Json<Game>) {
-    match get_id(params) {
-        Some(id) => match handler.pause_game(id).await {
-            Ok(g) => (StatusCode::OK, Json(g)),
-            Err(Error::NotFound { id: _ }) => (StatusCode::NOT_FOUND, Json(Game::default())),
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(Game::default())),
-        },
-        None => (StatusCode::BAD_REQUEST, Json(Game::default())),
-    }
+) -> Result<Json<Game>, ApiError> {
+    let id = get_id(&params)?;
+    Ok(Json(handler.pause_game(id).await?))
 }
 
 async fn resume_handler(
     handler: State<Handler>,
     Path(params): Path<HashMap<String, String>>,
-) -> (StatusCode, Json<Game>) {
-    match get_id(params) {
-        Some(id) => match handler.resume_game(id).await {
-            Ok(g) => (StatusCode::OK, Json(g)),
-            Err(Error::NotFound { id: _ }) => (StatusCode::NOT_FOUND, Json(Game::default())),
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(Game::default())),
-        },
-        None => (StatusCode::BAD_REQUEST, Json(Game::default())),
-    }
+) -> Result<Json<Game>, ApiError> {
+    let id = get_id(&params)?;
+    Ok(Json(handler.resume_game(id).await?))
+}
+
+async fn undo_handler(
+    handler: State<Handler>,
+    Path(params): Path<HashMap<String, String>>,
+) -> Result<Json<Game>, ApiError> {
+    let id = get_id(&params)?;
+    Ok(Json(handler.undo_game(id).await?))
+}
+
+async fn redo_handler(
+    handler: State<Handler>,
+    Path(params): Path<HashMap<String, String>>,
+) -> Result<Json<Game>, ApiError> {
+    let id = get_id(&params)?;
+    Ok(Json(handler.redo_game(id).await?))
+}
+
+async fn replay_handler(
+    handler: State<Handler>,
+    Path(params): Path<HashMap<String, String>>,
+) -> Result<Json<Replay>, ApiError> {
+    let id = get_id(&params)?;
+    Ok(Json(handler.replay(id).await?))
+}
+
+async fn replay_at_handler(
+    handler: State<Handler>,
+    Path(params): Path<HashMap<String, String>>,
+) -> Result<Json<ReplayFrame>, ApiError> {
+    let id = get_id(&params)?;
+    let n = params
+        .get("n")
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| ApiError::invalid_parameters("move number must be a whole number"))?;
+    Ok(Json(handler.replay_at(id, n).await?))
+}
+
+async fn hint_handler(
+    handler: State<Handler>,
+    Path(params): Path<HashMap<String, String>>,
+) -> Result<Json<Hint>, ApiError> {
+    let id = get_id(&params)?;
+    Ok(Json(handler.hint(id).await?))
+}
+
+async fn probabilities_handler(
+    handler: State<Handler>,
+    Path(params): Path<HashMap<String, String>>,
+) -> Result<Json<Probabilities>, ApiError> {
+    let id = get_id(&params)?;
+    Ok(Json(handler.probabilities(id).await?))
 }
 
 async fn reveal_handler(
     handler: State<Handler>,
     Path(params): Path<HashMap<String, String>>,
-    extract::Json(at): extract::Json<crate::handler::model::At>,
-) -> (StatusCode, Json<Game>) {
-    match get_id(params) {
-        Some(id) => match handler.reveal(id, (at.y, at.x)).await {
-            Ok(g) => (StatusCode::OK, Json(g)),
-            Err(Error::NotFound { id: _ }) => (StatusCode::NOT_FOUND, Json(Game::default())),
-            Err(Error::InvalidPoint { point: _ }) => {
-                (StatusCode::BAD_REQUEST, Json(Game::default()))
-            }
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(Game::default())),
-        },
-        None => (StatusCode::BAD_REQUEST, Json(Game::default())),
-    }
+    body: Result<extract::Json<At>, JsonRejection>,
+) -> Result<Json<Game>, ApiError> {
+    let id = get_id(&params)?;
+    let extract::Json(at) = body?;
+    Ok(Json(handler.reveal(id, (at.y, at.x)).await?))
+}
+
+async fn chord_handler(
+    handler: State<Handler>,
+    Path(params): Path<HashMap<String, String>>,
+    body: Result<extract::Json<At>, JsonRejection>,
+) -> Result<Json<Game>, ApiError> {
+    let id = get_id(&params)?;
+    let extract::Json(at) = body?;
+    Ok(Json(handler.chord(id, (at.y, at.x)).await?))
 }
 
 async fn mark_as_flag_handler(
     handler: State<Handler>,
     Path(params): Path<HashMap<String, String>>,
-    extract::Json(at): extract::Json<crate::handler::model::At>,
-) -> (StatusCode, Json<Game>) {
-    match get_id(params) {
-        Some(id) => match handler.mark_as_flag(id, (at.y, at.x)).await {
-            Ok(g) => (StatusCode::OK, Json(g)),
-            Err(Error::NotFound { id: _ }) => (StatusCode::NOT_FOUND, Json(Game::default())),
-            Err(Error::InvalidPoint { point: _ }) => {
-                (StatusCode::BAD_REQUEST, Json(Game::default()))
-            }
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(Game::default())),
-        },
-        None => (StatusCode::BAD_REQUEST, Json(Game::default())),
-    }
+    body: Result<extract::Json<At>, JsonRejection>,
+) -> Result<Json<Game>, ApiError> {
+    let id = get_id(&params)?;
+    let extract::Json(at) = body?;
+    Ok(Json(handler.mark_as_flag(id, (at.y, at.x)).await?))
 }
 
 async fn mark_as_question_handler(
     handler: State<Handler>,
     Path(params): Path<HashMap<String, String>>,
-    extract::Json(at): extract::Json<crate::handler::model::At>,
-) -> (StatusCode, Json<Game>) {
-    match get_id(params) {
-        Some(id) => match handler.mark_as_question(id, (at.y, at.x)).await {
-            Ok(g) => (StatusCode::OK, Json(g)),
-            Err(Error::NotFound { id: _ }) => (StatusCode::NOT_FOUND, Json(Game::default())),
-            Err(Error::InvalidPoint { point: _ }) => {
-                (StatusCode::BAD_REQUEST, Json(Game::default()))
-            }
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(Game::default())),
-        },
-        None => (StatusCode::BAD_REQUEST, Json(Game::default())),
-    }
+    body: Result<extract::Json<At>, JsonRejection>,
+) -> Result<Json<Game>, ApiError> {
+    let id = get_id(&params)?;
+    let extract::Json(at) = body?;
+    Ok(Json(handler.mark_as_question(id, (at.y, at.x)).await?))
 }
 
 async fn new_handler(
     handler: State<Handler>,
-    extract::Json(params): extract::Json<crate::handler::model::Create>,
-) -> (StatusCode, Json<Game>) {
-    if params.rows < 1
-        || params.columns < 1
-        || params.mines < 1
-        || params.mines >= params.rows * params.columns
-    {
-        return (StatusCode::BAD_REQUEST, Json(Game::default()));
+    body: Result<extract::Json<Create>, JsonRejection>,
+) -> Result<Json<Game>, ApiError> {
+    let extract::Json(params) = body?;
+    check_board(&handler.limits, params.rows, params.columns, params.mines)?;
+    if params.no_guess && i64::from(params.rows) * i64::from(params.columns) > MAX_NO_GUESS_CELLS {
+        return Err(ApiError::invalid_parameters(&format!(
+            "no_guess boards must have at most {MAX_NO_GUESS_CELLS} cells"
+        )));
     }
-    match handler
-        .new_game(params.rows, params.columns, params.mines)
-        .await
+    if params.duration_seconds < 0 {
+        return Err(ApiError::invalid_parameters(
+            "duration_seconds must not be negative",
+        ));
+    }
+    if params.wrap && params.topology == Topology::Hex && params.rows % 2 == 1 {
+        return Err(ApiError::invalid_parameters(
+            "wrapping hex boards need an even number of rows",
+        ));
+    }
+    let g = handler
+        .new_game(
+            params.rows,
+            params.columns,
+            params.mines,
+            map_to_options(&params),
+        )
+        .await?;
+    Ok(Json(g))
+}
+
+async fn import_handler(
+    handler: State<Handler>,
+    body: Result<extract::Json<Import>, JsonRejection>,
+) -> Result<Json<Game>, ApiError> {
+    let extract::Json(params) = body?;
+    if params
+        .title
+        .as_ref()
+        .is_some_and(|t| t.chars().count() > MAX_TITLE_LENGTH)
     {
-        Ok(g) => (StatusCode::OK, Json(g)),
-        Err(Error::NotFound { id: _ }) => (StatusCode::NOT_FOUND, Json(Game::default())),
-        _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(Game::default())),
+        return Err(ApiError::invalid_parameters(&format!(
+            "title must be at most {MAX_TITLE_LENGTH} characters"
+        )));
+    }
+    let lines: Vec<&str> = params
+        .layout
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    let rows = lines.len() as i32;
+    let columns = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as i32;
+    check_size(&handler.limits, rows, columns)?;
+    if params.wrap && params.topology == Topology::Hex && rows % 2 == 1 {
+        return Err(ApiError::invalid_parameters(
+            "wrapping hex boards need an even number of rows",
+        ));
+    }
+    let g = handler
+        .import_game(
+            params.layout.clone(),
+            params.title.clone().unwrap_or_default(),
+            map_to_import_options(&params),
+        )
+        .await?;
+    Ok(Json(g))
+}
+
+async fn daily_handler(
+    handler: State<Handler>,
+    body: Result<extract::Json<NewDaily>, JsonRejection>,
+) -> Result<Json<Game>, ApiError> {
+    let extract::Json(params) = body?;
+    let player = params.player.trim();
+    if player.is_empty() || player.chars().count() > MAX_PLAYER_LENGTH {
+        return Err(ApiError::invalid_parameters(&format!(
+            "player must be between 1 and {MAX_PLAYER_LENGTH} characters"
+        )));
     }
+    let g = handler
+        .daily_game(player.to_string(), params.difficulty)
+        .await?;
+    Ok(Json(g))
 }
 
-fn get_id(params: HashMap<String, String>) -> Option<uuid::Uuid> {
-    match params.get("id") {
-        Some(id) => match uuid::Uuid::parse_str(id) {
-            Ok(uuid) => Some(uuid),
-            Err(_) => None,
-        },
-        None => None,
+async fn new_race_handler(
+    handler: State<Handler>,
+    body: Result<extract::Json<NewRace>, JsonRejection>,
+) -> Result<Json<Race>, ApiError> {
+    let extract::Json(params) = body?;
+    check_board(&handler.limits, params.rows, params.columns, params.mines)?;
+    if params.players < 1 || params.players > MAX_RACE_PLAYERS {
+        return Err(ApiError::invalid_parameters(&format!(
+            "players must be between 1 and {MAX_RACE_PLAYERS}"
+        )));
     }
+    Ok(Json(handler.new_race(params).await?))
+}
+
+async fn race_handler(
+    handler: State<Handler>,
+    Path(params): Path<HashMap<String, String>>,
+) -> Result<Json<Race>, ApiError> {
+    let id = get_id(&params)?;
+    Ok(Json(handler.get_race(id).await?))
+}
+
+async fn join_race_handler(
+    handler: State<Handler>,
+    Path(params): Path<HashMap<String, String>>,
+    body: Result<extract::Json<JoinRace>, JsonRejection>,
+) -> Result<Json<Game>, ApiError> {
+    let id = get_id(&params)?;
+    let extract::Json(body) = body?;
+    let player = body.player.trim();
+    if player.is_empty() || player.chars().count() > MAX_PLAYER_LENGTH {
+        return Err(ApiError::invalid_parameters(&format!(
+            "player must be between 1 and {MAX_PLAYER_LENGTH} characters"
+        )));
+    }
+    Ok(Json(handler.join_race(id, player.to_string()).await?))
+}
+
+async fn race_standings_handler(
+    handler: State<Handler>,
+    Path(params): Path<HashMap<String, String>>,
+) -> Result<Json<Standings>, ApiError> {
+    let id = get_id(&params)?;
+    Ok(Json(handler.race_standings(id).await?))
+}
+
+async fn daily_results_handler(
+    handler: State<Handler>,
+    Path(params): Path<HashMap<String, String>>,
+) -> Result<Json<DailyResults>, ApiError> {
+    let date = params
+        .get("date")
+        .and_then(|date| parse_date(date))
+        .ok_or_else(|| ApiError::invalid_parameters("date must be YYYY-MM-DD"))?;
+    Ok(Json(handler.daily_results(date).await?))
+}
+
+/// Checks the board size against `limits` and that some cells are left without mines.
+fn check_board(limits: &Limits, rows: i32, columns: i32, mines: i32) -> Result<(), ApiError> {
+    check_size(limits, rows, columns)?;
+    if mines < 1 || i64::from(mines) >= i64::from(rows) * i64::from(columns) {
+        return Err(ApiError::invalid_parameters(
+            "mines must be at least 1 and fewer than the cells",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks the board fits `limits`; imported layouts count their own mines.
+fn check_size(limits: &Limits, rows: i32, columns: i32) -> Result<(), ApiError> {
+    if rows < 1 || columns < 1 || rows > limits.max_rows || columns > limits.max_columns {
+        return Err(ApiError::invalid_parameters(&format!(
+            "rows and columns must be between 1 and {}x{}",
+            limits.max_rows, limits.max_columns
+        )));
+    }
+    if i64::from(rows) * i64::from(columns) > i64::from(i32::MAX) {
+        return Err(ApiError::invalid_parameters("board has too many cells"));
+    }
+    Ok(())
+}
+
+/// Reads a YYYY-MM-DD date.
+fn parse_date(date: &str) -> Option<time::Date> {
+    let mut parts = date.splitn(3, '-').map(|part| part.parse::<i32>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    let month = time::Month::try_from(u8::try_from(month).ok()?).ok()?;
+    time::Date::from_calendar_date(year, month, u8::try_from(day).ok()?).ok()
+}
+
+pub(super) fn get_id(params: &HashMap<String, String>) -> Result<uuid::Uuid, ApiError> {
+    let id = params.get("id").map(String::as_str).unwrap_or_default();
+    uuid::Uuid::parse_str(id).map_err(|_| ApiError::invalid_id(id))
 }
 
 #[cfg(test)]
@@ -260,4 +619,14 @@ mod tests {
             game_id.to_string()
         );
     }
+
+    #[test]
+    fn raised_limits_when_check_board_then_rejects_overflowing_cells() {
+        let limits = Limits {
+            max_rows: i32::MAX,
+            max_columns: i32::MAX,
+        };
+        assert!(check_board(&limits, 100_000, 100_000, 10).is_err());
+        assert!(check_board(&limits, 1000, 1000, 10).is_ok());
+    }
 }
@@ -1,5 +1,19 @@
+#[allow(clippy::module_inception)]
 mod handler;
 pub use self::handler::Handler;
+pub use self::handler::Limits;
 
 mod model;
-pub use self::model::{At, Create, Game};
+pub use self::model::{
+    Action, At, Changed, Clicks, Command, Create, Daily, DailyResult, DailyResults, Difficulty,
+    FirstClick, Game, Hint, Import, JoinRace, Layout, NewDaily, NewRace, Participant,
+    Probabilities, Push, Race, Replay, ReplayFrame, ReplayMove, Spectated, Standing, Standings,
+    Topology, Update,
+};
+
+mod socket;
+
+mod spectate;
+
+mod error;
+pub use self::error::{ApiError, Details, ErrorBody};
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 
+use crate::handler::error::ErrorBody;
 use crate::service;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -8,30 +9,519 @@ pub struct Game {
     pub title: String,
     pub state: String,
     pub board_view: Vec<Vec<char>>,
+    pub layout: Layout,
+    pub seed: u64,
     pub duration_seconds: i32,
     pub elapsed_seconds: i32,
     pub score: i32,
     pub resumed_timestamp: Option<time::OffsetDateTime>,
+    /// Minimum clicks needed to clear the board, 0 before the first reveal.
+    pub bbbv: i32,
+    pub clicks: Clicks,
+    pub hints: i32,
+    pub practice: bool,
+    pub undos: i32,
+    pub daily: Option<Daily>,
+    /// Id of the race the game is a player slot of.
+    pub race: Option<String>,
 }
 
+/// The daily challenge a game is an attempt at, `date` as YYYY-MM-DD.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Daily {
+    pub date: String,
+    pub difficulty: String,
+    pub player: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Clicks {
+    pub reveal: i32,
+    pub chord: i32,
+    pub flag: i32,
+    pub question: i32,
+}
+
+/// The seed reads 0 until the layout may be shown, see `Game::shows_layout`, since it
+/// gives away the mines.
 pub(super) fn map_to_model(g: service::Game) -> Game {
     Game {
         id: g.id.to_string(),
         title: g.title.clone(),
         state: g.state.to_string(),
         board_view: g.get_board_view(),
+        layout: map_to_layout(g.topology, g.wrap),
+        seed: if g.shows_layout() { g.seed } else { 0 },
         duration_seconds: g.duration_seconds,
-        elapsed_seconds: g.elapsed_seconds,
+        elapsed_seconds: g.elapsed_seconds_at(time::OffsetDateTime::now_utc()),
         score: g.score,
         resumed_timestamp: g.resumed_timestamp,
+        bbbv: g.bbbv,
+        clicks: Clicks {
+            reveal: g.clicks.reveal,
+            chord: g.clicks.chord,
+            flag: g.clicks.flag,
+            question: g.clicks.question,
+        },
+        hints: g.hints,
+        practice: g.practice,
+        undos: g.undos,
+        daily: g.daily.map(|d| Daily {
+            date: d.date.to_string(),
+            difficulty: d.difficulty.to_string(),
+            player: d.player,
+        }),
+        race: g.race.map(|id| id.to_string()),
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How to draw `board_view`: cell shape, neighbors per cell, for hexagons which rows are
+/// shifted half a cell to the right, and whether opposite edges touch.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Layout {
+    pub topology: String,
+    pub neighbors: i32,
+    pub offset: Option<String>,
+    pub wrap: bool,
+}
+
+fn map_to_layout(topology: service::Topology, wrap: bool) -> Layout {
+    Layout {
+        topology: topology.to_string(),
+        neighbors: match topology {
+            service::Topology::Square => 8,
+            service::Topology::Hex => 6,
+        },
+        offset: match topology {
+            service::Topology::Square => None,
+            service::Topology::Hex => Some("odd-r".to_string()),
+        },
+        wrap,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Create {
     pub rows: i32,
     pub columns: i32,
     pub mines: i32,
+    #[serde(default)]
+    pub first_click: FirstClick,
+    #[serde(default)]
+    pub no_guess: bool,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub topology: Topology,
+    #[serde(default)]
+    pub wrap: bool,
+    /// Time limit in seconds, 0 for no limit.
+    #[serde(default)]
+    pub duration_seconds: i32,
+    /// Practice games allow undo and redo.
+    #[serde(default)]
+    pub practice: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Topology {
+    #[default]
+    Square,
+    Hex,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FirstClick {
+    #[default]
+    SafeCell,
+    SafeOpening,
+}
+
+pub(super) fn map_to_options(params: &Create) -> service::Options {
+    service::Options {
+        safe_area: match params.first_click {
+            FirstClick::SafeCell => service::SafeArea::Cell,
+            FirstClick::SafeOpening => service::SafeArea::Opening,
+        },
+        no_guess: params.no_guess,
+        seed: params.seed,
+        topology: match params.topology {
+            Topology::Square => service::Topology::Square,
+            Topology::Hex => service::Topology::Hex,
+        },
+        wrap: params.wrap,
+        duration_seconds: params.duration_seconds,
+        practice: params.practice,
+    }
+}
+
+/// A hand-made board, `layout` having one line per row: `.` for a safe cell, `*` for a
+/// mine and `o` for a safe cell that starts revealed.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Import {
+    pub layout: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub topology: Topology,
+    #[serde(default)]
+    pub wrap: bool,
+}
+
+pub(super) fn map_to_import_options(params: &Import) -> service::Options {
+    service::Options {
+        topology: match params.topology {
+            Topology::Square => service::Topology::Square,
+            Topology::Hex => service::Topology::Hex,
+        },
+        wrap: params.wrap,
+        ..Default::default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct NewDaily {
+    pub player: String,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+}
+
+/// Daily board size: beginner 9x9 with 10 mines, intermediate 16x16 with 40, expert 16x30
+/// with 99.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    #[default]
+    Beginner,
+    Intermediate,
+    Expert,
+}
+
+pub(super) fn map_to_difficulty(difficulty: Difficulty) -> service::Difficulty {
+    match difficulty {
+        Difficulty::Beginner => service::Difficulty::Beginner,
+        Difficulty::Intermediate => service::Difficulty::Intermediate,
+        Difficulty::Expert => service::Difficulty::Expert,
+    }
+}
+
+/// Finished attempts at the daily boards of `date`, ranked within each difficulty.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DailyResults {
+    pub date: String,
+    pub results: Vec<DailyResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DailyResult {
+    pub rank: i32,
+    /// Missing while the day's boards can still be played.
+    pub game_id: Option<String>,
+    pub player: String,
+    pub difficulty: String,
+    pub state: String,
+    pub score: i32,
+    pub elapsed_seconds: i32,
+    pub revealed: i32,
+}
+
+pub(super) fn map_to_daily_results(
+    date: time::Date,
+    results: Vec<service::DailyResult>,
+) -> DailyResults {
+    let mut rank = 0;
+    let mut previous = None;
+    let results = results
+        .into_iter()
+        .map(|r| {
+            if previous != Some(r.difficulty) {
+                rank = 0;
+                previous = Some(r.difficulty);
+            }
+            rank += 1;
+            DailyResult {
+                rank,
+                game_id: r.game_id.map(|id| id.to_string()),
+                player: r.player,
+                difficulty: r.difficulty.to_string(),
+                state: r.state.to_string(),
+                score: r.score,
+                elapsed_seconds: r.elapsed_seconds,
+                revealed: r.revealed,
+            }
+        })
+        .collect();
+    DailyResults {
+        date: date.to_string(),
+        results,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct NewRace {
+    pub rows: i32,
+    pub columns: i32,
+    pub mines: i32,
+    /// Player slots, each with its own game on the shared layout.
+    pub players: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct JoinRace {
+    pub player: String,
+}
+
+/// A race lobby. The seed is left out since it gives away the layout.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Race {
+    pub id: String,
+    pub rows: i32,
+    pub columns: i32,
+    pub mines: i32,
+    pub players: i32,
+    pub finished: Option<time::OffsetDateTime>,
+    /// Players who joined, in the order they did.
+    pub participants: Vec<Participant>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Participant {
+    pub player: String,
+    /// Missing until the race is decided, so nobody can watch an opponent's board.
+    pub game_id: Option<String>,
+    pub joined: Option<time::OffsetDateTime>,
+}
+
+pub(super) fn map_to_race(race: service::Race) -> Race {
+    Race {
+        id: race.id.to_string(),
+        rows: race.rows,
+        columns: race.cols,
+        mines: race.mines,
+        players: race.participants.len() as i32,
+        finished: race.finished,
+        participants: race
+            .participants
+            .into_iter()
+            .filter_map(|p| {
+                Some(Participant {
+                    player: p.player?,
+                    game_id: race.finished.map(|_| p.game_id.to_string()),
+                    joined: p.joined,
+                })
+            })
+            .collect(),
+    }
+}
+
+/// Players of a race ranked winners first, then by safe cells revealed, then by time.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Standings {
+    pub id: String,
+    pub finished: Option<time::OffsetDateTime>,
+    pub standings: Vec<Standing>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Standing {
+    pub rank: i32,
+    pub player: String,
+    /// Missing until the race is decided, as for `Participant`.
+    pub game_id: Option<String>,
+    pub state: String,
+    pub revealed: i32,
+    pub elapsed_seconds: i32,
+}
+
+pub(super) fn map_to_standings(
+    race: service::Race,
+    standings: Vec<service::Standing>,
+) -> Standings {
+    Standings {
+        id: race.id.to_string(),
+        finished: race.finished,
+        standings: standings
+            .into_iter()
+            .enumerate()
+            .map(|(i, s)| Standing {
+                rank: i as i32 + 1,
+                player: s.player,
+                game_id: race.finished.map(|_| s.game_id.to_string()),
+                state: s.state.to_string(),
+                revealed: s.revealed,
+                elapsed_seconds: s.elapsed_seconds,
+            })
+            .collect(),
+    }
+}
+
+/// A cell that can be proven safe or a mine, with the rule that proves it: "single_cell",
+/// "subset", "enumeration" or "mine_count".
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Hint {
+    pub x: i32,
+    pub y: i32,
+    pub mine: bool,
+    pub reason: String,
+}
+
+pub(super) fn map_to_hint(hint: service::Hint) -> Hint {
+    Hint {
+        x: hint.point.1,
+        y: hint.point.0,
+        mine: hint.mine,
+        reason: hint.reason.to_string(),
+    }
+}
+
+/// Mine probability of every cell, in the same rows and columns as `board_view`; `null`
+/// for revealed cells. `exact` is false when part of the board had to be sampled.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Probabilities {
+    pub probabilities: Vec<Vec<Option<f64>>>,
+    pub exact: bool,
+}
+
+pub(super) fn map_to_probabilities(p: service::Probabilities) -> Probabilities {
+    Probabilities {
+        probabilities: p.cells,
+        exact: p.exact,
+    }
+}
+
+/// Everything needed to play a game back. `mine_layout` has a row of `*` (mine) and `.`
+/// (safe) per board row, and is only given for finished games.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Replay {
+    pub id: String,
+    pub rows: i32,
+    pub columns: i32,
+    pub mines: i32,
+    pub layout: Layout,
+    pub mine_layout: Option<Vec<String>>,
+    pub moves: Vec<ReplayMove>,
+}
+
+/// A logged move: "cell_revealed", "chorded", "flagged", "questioned", "paused",
+/// "resumed", "undone", "redone", "hint_taken" or "timed_out", with its cell if any.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayMove {
+    pub kind: String,
+    pub at: Option<At>,
+    pub created: time::OffsetDateTime,
+}
+
+pub(super) fn map_to_replay(replay: service::Replay) -> Replay {
+    let mine_layout = replay.layout().map(|board| {
+        (0..board.rows())
+            .map(|i| {
+                board.cells()[(i * board.cols()) as usize..((i + 1) * board.cols()) as usize]
+                    .iter()
+                    .map(|c| if *c == service::Cell::Mine { '*' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    });
+    let g = &replay.game;
+    Replay {
+        id: g.id.to_string(),
+        rows: g.rows(),
+        columns: g.cols(),
+        mines: g.mines,
+        layout: map_to_layout(g.topology, g.wrap),
+        mine_layout,
+        moves: replay
+            .moves
+            .iter()
+            .map(|logged| ReplayMove {
+                kind: logged.event.to_string(),
+                at: logged.event.point().map(|p| {
+                    let (y, x) = p.into();
+                    At { x, y }
+                }),
+                created: logged.created,
+            })
+            .collect(),
+    }
+}
+
+/// One move or state change of a game streamed to spectators, with the game right after
+/// it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Spectated {
+    pub kind: String,
+    pub at: Option<At>,
+    pub created: time::OffsetDateTime,
+    pub game: Game,
+}
+
+pub(super) fn map_to_spectated(logged: &service::Logged, g: service::Game) -> Spectated {
+    Spectated {
+        kind: logged.event.to_string(),
+        at: logged.event.point().map(|p| {
+            let (y, x) = p.into();
+            At { x, y }
+        }),
+        created: logged.created,
+        game: map_to_model(g),
+    }
+}
+
+/// The board as the player saw it after move `n` of a replay.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ReplayFrame {
+    pub n: usize,
+    pub state: String,
+    pub board_view: Vec<Vec<char>>,
+}
+
+pub(super) fn map_to_replay_frame(n: usize, g: service::Game) -> ReplayFrame {
+    ReplayFrame {
+        n,
+        state: g.state.to_string(),
+        board_view: g.get_board_view(),
+    }
+}
+
+/// A move sent over a game's WebSocket.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Command {
+    pub action: Action,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Reveal,
+    Flag,
+    Question,
+    Chord,
+}
+
+/// Message pushed over a game's WebSocket after each command, tagged by `type`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Push {
+    Update(Box<Update>),
+    Error(ErrorBody),
+}
+
+/// The game after a move, with the cells of `board_view` that changed since the last push.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Update {
+    pub game: Game,
+    pub changed: Vec<Changed>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Changed {
+    pub x: i32,
+    pub y: i32,
+    pub value: char,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
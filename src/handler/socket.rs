@@ -0,0 +1,105 @@
+use crate::handler::error::ApiError;
+use crate::handler::handler::{get_id, Handler, HandlerTrait};
+use crate::handler::model::{Action, Changed, Command, Game, Push, Update};
+use crate::service::Error;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::response::Response;
+use std::collections::HashMap;
+
+/// Opens a WebSocket on an existing game. Each text message is a `Command`, answered
+/// with a `Push`.
+pub(super) async fn ws_handler(
+    handler: State<Handler>,
+    Path(params): Path<HashMap<String, String>>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let id = get_id(&params)?;
+    let game = handler.get_game(id).await?;
+    Ok(ws.on_upgrade(move |socket| play_socket(handler.0, id, game.board_view, socket)))
+}
+
+/// Plays the commands received on `socket` in order until it closes. `view` is the board
+/// last sent to the client, to find the cells each move changed.
+async fn play_socket(
+    handler: Handler,
+    id: uuid::Uuid,
+    mut view: Vec<Vec<char>>,
+    mut socket: WebSocket,
+) {
+    while let Some(Ok(message)) = socket.recv().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let push = match serde_json::from_str::<Command>(&text) {
+            Ok(command) => match play(&handler, id, command).await {
+                Ok(game) => {
+                    let changed = changed_cells(&view, &game.board_view);
+                    view.clone_from(&game.board_view);
+                    Push::Update(Box::new(Update { game, changed }))
+                }
+                Err(e) => Push::Error(ApiError::from(e).into_body()),
+            },
+            Err(e) => Push::Error(ApiError::invalid_message(e.to_string()).into_body()),
+        };
+        let reply = serde_json::to_string(&push).expect("push serializes");
+        if socket.send(Message::Text(reply)).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Runs a command through the same `HandlerTrait` calls as the REST routes.
+async fn play(handler: &Handler, id: uuid::Uuid, command: Command) -> Result<Game, Error> {
+    let point = (command.y, command.x);
+    match command.action {
+        Action::Reveal => handler.reveal(id, point).await,
+        Action::Flag => handler.mark_as_flag(id, point).await,
+        Action::Question => handler.mark_as_question(id, point).await,
+        Action::Chord => handler.chord(id, point).await,
+    }
+}
+
+fn changed_cells(before: &[Vec<char>], after: &[Vec<char>]) -> Vec<Changed> {
+    let mut changed = vec![];
+    for (y, row) in after.iter().enumerate() {
+        for (x, value) in row.iter().enumerate() {
+            if before.get(y).and_then(|r| r.get(x)) != Some(value) {
+                changed.push(Changed {
+                    x: x as i32,
+                    y: y as i32,
+                    value: *value,
+                });
+            }
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flagged_and_opened_cells_when_changed_cells_then_lists_them() {
+        let before = vec![vec![' ', ' '], vec![' ', ' ']];
+        let after = vec![vec!['!', ' '], vec![' ', '1']];
+        assert_eq!(
+            changed_cells(&before, &after),
+            [
+                Changed {
+                    x: 0,
+                    y: 0,
+                    value: '!'
+                },
+                Changed {
+                    x: 1,
+                    y: 1,
+                    value: '1'
+                }
+            ]
+        );
+    }
+}
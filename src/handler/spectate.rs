@@ -0,0 +1,46 @@
+use crate::handler::error::ApiError;
+use crate::handler::handler::{get_id, Handler};
+use crate::handler::model::map_to_spectated;
+use crate::service::Broadcast;
+use axum::extract::{Path, State};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures_util::stream::{self, Stream, StreamExt};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Streams the moves and state changes of an existing game as server-sent events, one
+/// `Spectated` per logged event named after its kind. The stream ends with the game.
+pub(super) async fn events_handler(
+    handler: State<Handler>,
+    Path(params): Path<HashMap<String, String>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let id = get_id(&params)?;
+    let receiver = handler.subscribe(id).await?;
+    let updates = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(update) => return Some((update, receiver)),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    let events = updates.flat_map(|update| stream::iter(to_events(update)));
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+fn to_events(update: Broadcast) -> Vec<Result<Event, Infallible>> {
+    update
+        .events
+        .iter()
+        .map(|logged| {
+            let spectated = map_to_spectated(logged, update.game.clone());
+            let event = Event::default()
+                .event(spectated.kind.clone())
+                .json_data(&spectated)
+                .expect("spectated serializes");
+            Ok(event)
+        })
+        .collect()
+}
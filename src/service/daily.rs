@@ -0,0 +1,154 @@
+use crate::service::State;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::fmt;
+
+/// Board size of a daily challenge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Difficulty {
+    #[default]
+    Beginner,
+    Intermediate,
+    Expert,
+}
+
+impl Difficulty {
+    /// Rows, columns and mines.
+    pub fn dimensions(self) -> (i32, i32, i32) {
+        match self {
+            Difficulty::Beginner => (9, 9, 10),
+            Difficulty::Intermediate => (16, 16, 40),
+            Difficulty::Expert => (16, 30, 99),
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difficulty::Beginner => write!(f, "beginner"),
+            Difficulty::Intermediate => write!(f, "intermediate"),
+            Difficulty::Expert => write!(f, "expert"),
+        }
+    }
+}
+
+/// Marks a game as a player's attempt at the board of `date` and `difficulty`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Daily {
+    pub date: time::Date,
+    pub difficulty: Difficulty,
+    pub player: String,
+}
+
+/// A finished daily attempt as ranked by `rank`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DailyResult {
+    /// Left out until the day is over, since the game would give the board away.
+    pub game_id: Option<uuid::Uuid>,
+    pub player: String,
+    pub difficulty: Difficulty,
+    pub state: State,
+    pub score: i32,
+    pub elapsed_seconds: i32,
+    /// Safe cells revealed, the opening included.
+    pub revealed: i32,
+}
+
+/// Seed of the board every player gets for `date` and `difficulty`, drawn from a ChaCha
+/// stream keyed by the server's `secret` so nobody can work it out ahead of the day.
+pub(super) fn seed(secret: &str, date: time::Date, difficulty: Difficulty) -> u64 {
+    let mut key = [0u8; 32];
+    for chunk in secret.as_bytes().chunks(key.len()) {
+        key.iter_mut().zip(chunk).for_each(|(k, b)| *k ^= b);
+        ChaCha8Rng::from_seed(key).fill_bytes(&mut key);
+    }
+    let mut rng = ChaCha8Rng::from_seed(key);
+    rng.set_stream(((date.to_julian_day() as u64) << 8) | difficulty as u64);
+    rng.next_u64()
+}
+
+/// Whether `seed` is the board of any daily challenge of `date`.
+pub(super) fn is_daily_seed(secret: &str, date: time::Date, seed: u64) -> bool {
+    [
+        Difficulty::Beginner,
+        Difficulty::Intermediate,
+        Difficulty::Expert,
+    ]
+    .into_iter()
+    .any(|d| self::seed(secret, date, d) == seed)
+}
+
+/// Orders attempts by difficulty, then won games by score and time, then the rest by
+/// safe cells revealed and time played.
+pub fn rank(results: &mut [DailyResult]) {
+    results.sort_by_key(|r| {
+        (
+            r.difficulty as i32,
+            r.state != State::Won,
+            -r.score,
+            -r.revealed,
+            r.elapsed_seconds,
+        )
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(
+        difficulty: Difficulty,
+        state: State,
+        score: i32,
+        elapsed: i32,
+        revealed: i32,
+    ) -> DailyResult {
+        DailyResult {
+            game_id: Some(uuid::Uuid::new_v4()),
+            player: format!("{difficulty}-{state}-{revealed}"),
+            difficulty,
+            state,
+            score,
+            elapsed_seconds: elapsed,
+            revealed,
+        }
+    }
+
+    #[test]
+    fn other_secret_when_seed_then_other_board() {
+        let date = time::Date::from_calendar_date(2026, time::Month::March, 1).unwrap();
+        let a = seed("a", date, Difficulty::Expert);
+        assert_eq!(a, seed("a", date, Difficulty::Expert));
+        assert_ne!(a, seed("b", date, Difficulty::Expert));
+        assert_ne!(a, seed("a", date, Difficulty::Beginner));
+        assert!(is_daily_seed("a", date, a));
+        assert!(!is_daily_seed("b", date, a));
+    }
+
+    #[test]
+    fn mixed_attempts_when_rank_then_won_by_score_first() {
+        let mut results = vec![
+            result(Difficulty::Expert, State::Won, 900, 300, 381),
+            result(Difficulty::Beginner, State::Lost, 0, 5, 20),
+            result(Difficulty::Beginner, State::Won, 400, 60, 71),
+            result(Difficulty::Beginner, State::Timeout, 0, 60, 40),
+            result(Difficulty::Beginner, State::Won, 700, 90, 71),
+            result(Difficulty::Beginner, State::Lost, 0, 30, 40),
+        ];
+        rank(&mut results);
+        let order: Vec<&str> = results.iter().map(|r| r.player.as_str()).collect();
+        assert_eq!(
+            order,
+            [
+                "beginner-won-71",
+                "beginner-won-71",
+                "beginner-lost-40",
+                "beginner-timeout-40",
+                "beginner-lost-20",
+                "expert-won-381"
+            ]
+        );
+        assert_eq!((results[0].score, results[1].score), (700, 400));
+    }
+}
@@ -4,10 +4,52 @@ use thiserror::Error;
 pub enum Error {
     #[error("database error")]
     Database(#[from] tokio_postgres::error::Error),
+    #[error("database pool error")]
+    Pool(#[from] deadpool_postgres::PoolError),
     #[error("refinery error")]
     Refinery(#[from] refinery::Error),
     #[error("not found id {id:?}")]
     NotFound { id: String },
     #[error("invalid point {point:?}")]
     InvalidPoint { point: (i32, i32) },
+    #[error("game paused {id:?}")]
+    GamePaused { id: String },
+    #[error("game {id:?} changed by another request")]
+    WriteConflict { id: String },
+    #[error("game over {id:?}")]
+    GameOver { id: String },
+    #[error("cell already revealed {point:?}")]
+    CellAlreadyRevealed { point: (i32, i32) },
+    #[error("cell flagged {point:?}")]
+    CellFlagged { point: (i32, i32) },
+    #[error("undo disabled for ranked game {id:?}")]
+    UndoDisabled { id: String },
+    #[error("nothing to undo {id:?}")]
+    NothingToUndo { id: String },
+    #[error("nothing to redo {id:?}")]
+    NothingToRedo { id: String },
+    #[error("no hint for {id:?}")]
+    NoHint { id: String },
+    #[error("{player:?} already played the daily board of {date}")]
+    DailyAlreadyPlayed { player: String, date: String },
+    #[error("seed {seed} is reserved for a daily board")]
+    ReservedSeed { seed: u64 },
+    #[error("invalid layout: {reason}")]
+    InvalidLayout { reason: String },
+    #[error("no no-guess layout found for {id:?}")]
+    NoGuessUnavailable { id: String },
+    #[error("probabilities disabled for ranked game {id:?}")]
+    ProbabilitiesDisabled { id: String },
+    #[error("board too large for probabilities {id:?}")]
+    ProbabilitiesUnavailable { id: String },
+    #[error("no move {n} in {id:?}")]
+    NoSuchMove { id: String, n: usize },
+    #[error("race full {id:?}")]
+    RaceFull { id: String },
+    #[error("race finished {id:?}")]
+    RaceFinished { id: String },
+    #[error("hints and probabilities disabled for race game {id:?}")]
+    AssistanceDisabled { id: String },
+    #[error("{player:?} already joined race {id:?}")]
+    PlayerAlreadyJoined { player: String, id: String },
 }
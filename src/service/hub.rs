@@ -0,0 +1,129 @@
+use crate::service::{Game, Logged};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// Updates buffered for a slow subscriber before it starts missing some.
+const CHANNEL_CAPACITY: usize = 64;
+
+/// A stored game update: the game as saved and the events that led to it.
+#[derive(Debug, Clone)]
+pub struct Broadcast {
+    pub game: Game,
+    pub events: Vec<Logged>,
+}
+
+/// A game's subscribers and the version of the last update they were sent.
+#[derive(Debug)]
+struct Channel {
+    sender: broadcast::Sender<Broadcast>,
+    version: i32,
+}
+
+/// Fans game updates out to the subscribers of each game in this process. Channels are
+/// opened by the first subscriber and closed once the game ends or nobody listens, the
+/// latter checked on every publish and subscribe.
+/// Updates already sent are skipped, so the same change can arrive both from this
+/// process and from a notification without reaching subscribers twice.
+#[derive(Debug, Clone, Default)]
+pub struct Hub {
+    channels: Arc<Mutex<HashMap<uuid::Uuid, Channel>>>,
+}
+
+impl Hub {
+    /// Subscribes to the updates of `game` after its current version. The receiver of a
+    /// finished game is already closed. Channels whose subscribers all left are dropped
+    /// first, as games nobody plays anymore never publish again.
+    pub fn subscribe(&self, game: &Game) -> broadcast::Receiver<Broadcast> {
+        if game.state.is_over() {
+            return broadcast::channel(1).1;
+        }
+        let mut channels = self.channels.lock().unwrap();
+        channels.retain(|_, c| c.sender.receiver_count() > 0);
+        let channel = channels.entry(game.id).or_insert_with(|| Channel {
+            sender: broadcast::channel(CHANNEL_CAPACITY).0,
+            version: game.version,
+        });
+        channel.sender.subscribe()
+    }
+
+    /// Sends `game` and its pending events to its subscribers, if any and if they have
+    /// not seen them yet.
+    pub fn publish(&self, game: &Game) {
+        let mut channels = self.channels.lock().unwrap();
+        let Some(channel) = channels.get_mut(&game.id) else {
+            return;
+        };
+        let version = game.version + game.events.len() as i32;
+        if version <= channel.version {
+            return;
+        }
+        channel.version = version;
+        let _ = channel.sender.send(Broadcast {
+            game: game.clone(),
+            events: game.events.clone(),
+        });
+        if game.state.is_over() || channel.sender.receiver_count() == 0 {
+            channels.remove(&game.id);
+        }
+    }
+
+    /// Version last sent to the subscribers of the game, `None` if it has none.
+    pub fn version(&self, game_id: uuid::Uuid) -> Option<i32> {
+        let channels = self.channels.lock().unwrap();
+        channels.get(&game_id).map(|c| c.version)
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::model::Point;
+    use crate::service::{Event, Move, State};
+
+    #[tokio::test]
+    async fn repeated_and_finished_updates_when_publish_then_sent_once_and_channel_closed() {
+        let hub = Hub::default();
+        let mut g = Game::new(1, 2, 1);
+        g.board = Some(g.layout_with_mines(&[Point(0, 1)]));
+        let mut receiver = hub.subscribe(&g);
+        hub.publish(&Game::new(2, 2, 1));
+
+        let now = time::OffsetDateTime::now_utc();
+        g.apply(Move::Flag(Point(0, 1)), now).unwrap();
+        hub.publish(&g);
+        hub.publish(&g);
+        g.version += g.events.len() as i32;
+        g.events.clear();
+        g.apply(Move::Reveal(Point(0, 0)), now).unwrap();
+        hub.publish(&g);
+        assert_eq!(hub.channels(), 0);
+
+        let update = receiver.recv().await.unwrap();
+        assert!(matches!(
+            update.events[..],
+            [Logged {
+                event: Event::Flagged(_),
+                ..
+            }]
+        ));
+        let update = receiver.recv().await.unwrap();
+        assert_eq!(update.game.state, State::Won);
+        assert_eq!(update.events.len(), 1);
+        assert!(receiver.recv().await.is_err());
+    }
+
+    #[test]
+    fn abandoned_channel_when_subscribe_then_dropped() {
+        let hub = Hub::default();
+        let (a, b) = (Game::new(2, 2, 1), Game::new(2, 2, 1));
+        drop(hub.subscribe(&a));
+        let _receiver = hub.subscribe(&b);
+        assert_eq!(hub.channels(), 1);
+        assert_eq!(hub.version(a.id), None);
+    }
+}
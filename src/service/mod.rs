@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 mod service;
 pub use self::service::DatabaseTrait;
 pub use self::service::Service;
@@ -7,8 +8,38 @@ pub use self::service::ServiceTrait;
 pub use self::service::MockServiceTrait;
 
 mod model;
+pub use self::model::Board;
+pub use self::model::Cell;
+pub use self::model::CellState;
+pub use self::model::Clicks;
+pub use self::model::Event;
 pub use self::model::Game;
+pub use self::model::Logged;
+pub use self::model::Mark;
+pub use self::model::Move;
+pub use self::model::Options;
+pub use self::model::Played;
+pub use self::model::SafeArea;
 pub use self::model::State;
+pub use self::model::Topology;
+
+mod daily;
+pub use self::daily::{rank, Daily, DailyResult, Difficulty};
+
+mod hub;
+pub use self::hub::{Broadcast, Hub};
+
+mod probability;
+pub use self::probability::Probabilities;
+
+mod race;
+pub use self::race::{Participant, Race, Standing};
+
+mod replay;
+pub use self::replay::Replay;
+
+mod solver;
+pub use self::solver::{Hint, Reason};
 
 mod error;
 pub use self::error::Error;
@@ -1,171 +1,1099 @@
-use rand::Rng;
+use crate::service::daily::{self, Daily, Difficulty};
+use crate::service::probability::{self, Probabilities};
+use crate::service::solver::{self, Hint};
+use crate::service::Error;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::VecDeque;
 use std::fmt;
 
+/// Version of the seeded layout algorithm; bump it whenever `new_board` changes its output.
+pub const RNG_VERSION: i32 = 1;
+
+/// Candidate layouts tried by no-guess generation before giving up.
+const MAX_NO_GUESS_ATTEMPTS: u64 = 1000;
+
 #[derive(Debug, Clone)]
 pub struct Game {
     pub id: uuid::Uuid,
     pub created: time::OffsetDateTime,
     pub updated: time::OffsetDateTime,
     pub title: String,
-    pub board: Vec<Vec<char>>,
-    pub player_board: Vec<Vec<char>>,
+    /// Mine layout, `None` until the first reveal places the mines.
+    pub board: Option<Board<Cell>>,
+    pub player_board: Board<CellState>,
+    pub mines: i32,
+    /// Safe cells not yet revealed, kept up to date by `reveal_at`.
+    pub safe_remaining: i32,
+    pub safe_area: SafeArea,
+    pub no_guess: bool,
+    pub topology: Topology,
+    /// Opposite edges are adjacent, making the board a torus.
+    pub wrap: bool,
+    pub seed: u64,
+    pub rng_version: i32,
     pub state: State,
     pub duration_seconds: i32,
     pub elapsed_seconds: i32,
     pub score: i32,
     pub resumed_timestamp: Option<time::OffsetDateTime>,
+    /// When the game was won, lost or timed out, `None` while it can still be played.
+    pub finished: Option<time::OffsetDateTime>,
+    /// Minimum clicks needed to clear the layout, 0 until generated.
+    pub bbbv: i32,
+    pub clicks: Clicks,
+    /// Hints given so far, see `take_hint`.
+    pub hints: i32,
+    /// Practice games allow undo and redo but are not ranked.
+    pub practice: bool,
+    pub undos: i32,
+    /// Moves in the order they were played, undone ones last.
+    pub history: Vec<Played>,
+    /// Sequence number of the last stored event.
+    pub version: i32,
+    /// Events since the game was read, appended to the log by the next write.
+    pub events: Vec<Logged>,
+    /// Set for daily challenge attempts, see `Game::daily`.
+    pub daily: Option<Daily>,
+    /// Race the game is a player slot of, see `Race`.
+    pub race: Option<uuid::Uuid>,
+    /// Keeps the seed and layout hidden after the game ends while others may still play
+    /// the same board. Not stored, worked out whenever the game is read.
+    pub layout_secret: bool,
+}
+
+/// Successful player moves by type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Clicks {
+    pub reveal: i32,
+    pub chord: i32,
+    pub flag: i32,
+    pub question: i32,
+}
+
+impl Clicks {
+    pub fn total(&self) -> i32 {
+        self.reveal + self.chord + self.flag + self.question
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Point(pub(super) i32, pub(super) i32);
+
+impl From<Point> for (i32, i32) {
+    fn from(p: Point) -> Self {
+        (p.0, p.1)
+    }
+}
+
+/// Content of a cell in the mine layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Mine,
+    /// Safe cell with the number of adjacent mines.
+    Empty(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark {
+    Flag,
+    Question,
+}
+
+/// What the player knows about a cell.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CellState {
+    #[default]
+    Hidden,
+    Marked(Mark),
+    Revealed,
+}
+
+/// Row-major grid of cells addressed by `Point`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Board<T> {
+    rows: i32,
+    cols: i32,
+    cells: Vec<T>,
+}
+
+impl<T: Copy> Board<T> {
+    pub fn new(rows: i32, cols: i32, value: T) -> Self {
+        Self {
+            rows,
+            cols,
+            cells: vec![value; (rows * cols) as usize],
+        }
+    }
+
+    pub fn from_cells(rows: i32, cols: i32, cells: Vec<T>) -> Self {
+        assert_eq!(cells.len(), (rows * cols) as usize);
+        Self { rows, cols, cells }
+    }
+
+    pub fn rows(&self) -> i32 {
+        self.rows
+    }
+
+    pub fn cols(&self) -> i32 {
+        self.cols
+    }
+
+    pub fn cells(&self) -> &[T] {
+        &self.cells
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        point.0 >= 0 && point.0 < self.rows && point.1 >= 0 && point.1 < self.cols
+    }
+
+    pub fn get(&self, point: Point) -> T {
+        self.cells[self.index(point)]
+    }
+
+    pub fn set(&mut self, point: Point, value: T) {
+        let index = self.index(point);
+        self.cells[index] = value;
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = Point> {
+        let cols = self.cols;
+        (0..self.rows).flat_map(move |i| (0..cols).map(move |j| Point(i, j)))
+    }
+
+    fn index(&self, point: Point) -> usize {
+        assert!(self.contains(point));
+        (point.0 * self.cols + point.1) as usize
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct Point(i32, i32);
+/// Area around the first revealed cell that is guaranteed to be mine-free.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SafeArea {
+    #[default]
+    Cell,
+    Opening,
+}
+
+/// Cell shape, which decides the neighbors of each cell.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Topology {
+    #[default]
+    Square,
+    /// Pointy-top hexagons in "odd-r" offset coordinates: odd rows are shifted half a
+    /// cell to the right.
+    Hex,
+}
+
+impl Topology {
+    fn directions(&self, point: Point) -> &'static [(i32, i32)] {
+        match self {
+            Topology::Square => &[
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ],
+            Topology::Hex if point.0 % 2 == 0 => {
+                &[(-1, -1), (-1, 0), (0, -1), (0, 1), (1, -1), (1, 0)]
+            }
+            Topology::Hex => &[(-1, 0), (-1, 1), (0, -1), (0, 1), (1, 0), (1, 1)],
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Topology::Square => write!(f, "square"),
+            Topology::Hex => write!(f, "hex"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub safe_area: SafeArea,
+    /// Only accept layouts that can be solved from the first click without guessing.
+    pub no_guess: bool,
+    /// Seed for the layout; a random one is picked when absent.
+    pub seed: Option<u64>,
+    pub topology: Topology,
+    pub wrap: bool,
+    /// Time limit in seconds of play, 0 for untimed games.
+    pub duration_seconds: i32,
+    pub practice: bool,
+}
 
 impl Game {
     pub fn new(rows: i32, cols: i32, mines: i32) -> Self {
+        Self::with_options(rows, cols, mines, Options::default())
+    }
+
+    /// Creates a game whose mines are placed on the first reveal, see `generate_at`.
+    pub fn with_options(rows: i32, cols: i32, mines: i32, options: Options) -> Self {
         assert!(rows > 0 && cols > 0 && mines > 0);
-        let (board, player_board) = Self::new_boards(rows, cols, mines);
+        assert!(mines < rows * cols);
         Self {
             id: uuid::Uuid::new_v4(),
             created: time::OffsetDateTime::now_utc(),
             updated: time::OffsetDateTime::now_utc(),
             title: "".to_string(),
-            board: board,
-            player_board: player_board,
+            board: None,
+            player_board: Board::new(rows, cols, CellState::Hidden),
+            mines,
+            safe_remaining: rows * cols - mines,
+            safe_area: options.safe_area,
+            no_guess: options.no_guess,
+            topology: options.topology,
+            wrap: options.wrap,
+            seed: options.seed.unwrap_or_else(|| rand::thread_rng().gen()),
+            rng_version: RNG_VERSION,
             state: State::New,
-            duration_seconds: 0,
+            duration_seconds: options.duration_seconds,
             elapsed_seconds: 0,
             score: 0,
             resumed_timestamp: None,
+            finished: None,
+            bbbv: 0,
+            clicks: Clicks::default(),
+            hints: 0,
+            practice: options.practice,
+            undos: 0,
+            history: vec![],
+            version: 0,
+            events: vec![],
+            daily: None,
+            race: None,
+            layout_secret: false,
         }
     }
 
+    /// The daily challenge board of `date` for `player`, keyed by the server's `secret`.
+    /// Everyone gets the same layout, so it is generated up front around the center cell,
+    /// which starts revealed.
+    pub fn daily(date: time::Date, difficulty: Difficulty, player: String, secret: &str) -> Self {
+        let (rows, cols, mines) = difficulty.dimensions();
+        let seed = daily::seed(secret, date, difficulty);
+        let mut g = Self::opened(rows, cols, mines, Some(seed));
+        g.daily = Some(Daily {
+            date,
+            difficulty,
+            player,
+        });
+        g
+    }
+
+    /// Creates the first player slot of race `race_id` on a random layout, see `Race::new`.
+    pub fn race(race_id: uuid::Uuid, rows: i32, cols: i32, mines: i32) -> Self {
+        let mut g = Self::opened(rows, cols, mines, None);
+        g.race = Some(race_id);
+        g
+    }
+
+    /// Generates the layout of `seed` around the center cell and reveals it, so everyone
+    /// given the same seed starts from the same opening.
+    fn opened(rows: i32, cols: i32, mines: i32, seed: Option<u64>) -> Self {
+        let options = Options {
+            safe_area: SafeArea::Opening,
+            seed,
+            ..Default::default()
+        };
+        let mut g = Self::with_options(rows, cols, mines, options);
+        let center = Point(rows / 2, cols / 2);
+        g.generate_at(center)
+            .expect("only no-guess generation gives up");
+        g.reveal_at(center);
+        g.bbbv = g.count_bbbv();
+        g.log(Event::Prerevealed(center), g.created);
+        g
+    }
+
+    /// Creates a game from a hand-made layout, one line per row: `.` for a safe cell, `*`
+    /// for a mine and `o` for a safe cell that starts revealed. Numbers are worked out from
+    /// the mines as for generated layouts.
+    pub fn from_layout(layout: &str, options: Options) -> Result<Self, Error> {
+        let invalid = |reason: String| Err(Error::InvalidLayout { reason });
+        let lines: Vec<&str> = layout
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect();
+        let cols = lines.first().map_or(0, |l| l.chars().count());
+        if cols == 0 {
+            return invalid("layout is empty".to_string());
+        }
+        if lines.iter().any(|l| l.chars().count() != cols) {
+            return invalid("rows must all have the same length".to_string());
+        }
+        let (mut mines, mut revealed) = (vec![], vec![]);
+        for (i, line) in lines.iter().enumerate() {
+            for (j, c) in line.chars().enumerate() {
+                let p = Point(i as i32, j as i32);
+                match c {
+                    '.' => {}
+                    '*' => mines.push(p),
+                    'o' => revealed.push(p),
+                    _ => return invalid(format!("unexpected {c:?} at row {i}, column {j}")),
+                }
+            }
+        }
+        if mines.is_empty() || mines.len() >= lines.len() * cols {
+            return invalid("mines must be at least 1 and fewer than the cells".to_string());
+        }
+        let mut g =
+            Self::with_options(lines.len() as i32, cols as i32, mines.len() as i32, options);
+        g.board = Some(g.layout_with_mines(&mines));
+        for p in revealed {
+            g.reveal_at(p);
+            g.log(Event::Prerevealed(p), g.created);
+        }
+        g.bbbv = g.count_bbbv();
+        if g.is_all_revealed() {
+            return invalid("layout must leave a safe cell hidden".to_string());
+        }
+        Ok(g)
+    }
+
     pub fn new_point(&self, point: (i32, i32)) -> Option<Point> {
         let p = Point(point.0, point.1);
-        if Self::inside_board(self.board.len() as i32, self.board[0].len() as i32, p) {
-            return Some(Point(point.0, point.1));
+        if self.player_board.contains(p) {
+            return Some(p);
         }
         None
     }
 
+    pub fn rows(&self) -> i32 {
+        self.player_board.rows()
+    }
+
+    pub fn cols(&self) -> i32 {
+        self.player_board.cols()
+    }
+
+    /// Whether the seed and mine layout may be shown: once the game is over, unless its
+    /// board is still being played by others.
+    pub fn shows_layout(&self) -> bool {
+        self.state.is_over() && !self.layout_secret
+    }
+
+    /// Mines are only placed once the first cell is revealed; until then `board` is `None`.
+    pub fn is_generated(&self) -> bool {
+        self.board.is_some()
+    }
+
+    /// Places the mines keeping `point` (and its neighbors for `SafeArea::Opening`) clear.
+    /// No-guess games always start on an opening and retry until the solver wins the board.
+    /// The layout only depends on the seed, dimensions, mines, options and `point`. Fails,
+    /// leaving the game without a layout, when no solvable board turns up in time.
+    pub fn generate_at(&mut self, point: Point) -> Result<(), Error> {
+        let start = std::time::Instant::now();
+        let (rows, cols) = (self.rows(), self.cols());
+        let mut safe_points = vec![point];
+        if self.safe_area == SafeArea::Opening || self.no_guess {
+            let adjacent = self.adjacent(point);
+            if rows * cols - (adjacent.len() as i32 + 1) >= self.mines {
+                safe_points.extend(adjacent);
+            }
+        }
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            self.board = Some(self.new_board(&mut rng, &safe_points));
+            if !self.no_guess || solver::is_solvable(self, point) {
+                break;
+            }
+            if attempts == MAX_NO_GUESS_ATTEMPTS {
+                tracing::warn!("no-guess generation gave up after {} attempts", attempts);
+                metrics::counter!("board_generation_failures_total").increment(1);
+                self.board = None;
+                return Err(Error::NoGuessUnavailable {
+                    id: self.id.to_string(),
+                });
+            }
+        }
+        self.bbbv = self.count_bbbv();
+        let labels = [("no_guess", self.no_guess.to_string())];
+        metrics::counter!("board_generation_attempts_total", &labels).increment(attempts);
+        metrics::histogram!("board_generation_duration_seconds", &labels)
+            .record(start.elapsed().as_secs_f64());
+        Ok(())
+    }
+
+    /// Player view of the board: ' ' hidden, '!' flag, '?' question, 'x' mine, '0'-'8' number.
     pub fn get_board_view(&self) -> Vec<Vec<char>> {
         let mut board_view = vec![];
-        for i in 0..self.board.len() {
+        for i in 0..self.rows() {
             let mut row = vec![];
-            for j in 0..self.board[i].len() {
-                if self.player_board[i][j] == 'v' {
-                    row.push(self.board[i][j]);
-                } else if self.player_board[i][j] == 'h' {
-                    row.push(' ');
-                } else {
-                    row.push(self.player_board[i][j]);
-                }
+            for j in 0..self.cols() {
+                let p = Point(i, j);
+                row.push(match self.player_board.get(p) {
+                    CellState::Hidden => ' ',
+                    CellState::Marked(Mark::Flag) => '!',
+                    CellState::Marked(Mark::Question) => '?',
+                    CellState::Revealed => match self.layout().get(p) {
+                        Cell::Mine => 'x',
+                        Cell::Empty(n) => char::from(b'0' + n),
+                    },
+                });
             }
             board_view.push(row);
         }
         board_view
     }
 
-    fn inside_board(rows: i32, cols: i32, point: Point) -> bool {
-        point.0 >= 0 && point.0 < rows && point.1 >= 0 && point.1 < cols
+    /// Safe cells revealed so far, pre-revealed ones included.
+    pub fn revealed(&self) -> i32 {
+        self.rows() * self.cols() - self.mines - self.safe_remaining
     }
 
-    fn adjacent_points(rows: i32, cols: i32, point: Point) -> Vec<Point> {
-        let mut adjacent_points = vec![];
-        let directions = vec![
-            (-1, -1),
-            (-1, 0),
-            (-1, 1),
-            (0, -1),
-            (0, 1),
-            (1, -1),
-            (1, 0),
-            (1, 1),
-        ];
-        for direction in directions {
-            let new_point = Point(point.0 + direction.0, point.1 + direction.1);
-            if Self::inside_board(rows, cols, new_point) {
-                adjacent_points.push(new_point);
+    /// Scans the boards for hidden safe cells, used to restore `safe_remaining`.
+    pub fn count_safe_remaining(&self) -> i32 {
+        match &self.board {
+            Some(layout) => self
+                .points()
+                .filter(|p| layout.get(*p) != Cell::Mine && !self.is_revealed_at(*p))
+                .count() as i32,
+            None => self.rows() * self.cols() - self.mines,
+        }
+    }
+
+    /// Rebuilds the layout from a row-major mine mask, recomputing the numbers.
+    pub fn set_mines(&mut self, mines: &[bool]) {
+        let points: Vec<Point> = self
+            .points()
+            .zip(mines)
+            .filter(|(_, mine)| **mine)
+            .map(|(p, _)| p)
+            .collect();
+        self.board = Some(self.layout_with_mines(&points));
+    }
+
+    pub(super) fn points(&self) -> impl Iterator<Item = Point> {
+        self.player_board.points()
+    }
+
+    pub(super) fn adjacent(&self, point: Point) -> Vec<Point> {
+        let (rows, cols) = (self.rows(), self.cols());
+        let mut adjacent = vec![];
+        for d in self.topology.directions(point) {
+            let mut p = Point(point.0 + d.0, point.1 + d.1);
+            if self.wrap {
+                p = Point(p.0.rem_euclid(rows), p.1.rem_euclid(cols));
+                // Narrow boards wrap onto the same cell from several directions.
+                if p == point || adjacent.contains(&p) {
+                    continue;
+                }
+            }
+            if self.player_board.contains(p) {
+                adjacent.push(p);
             }
         }
-        adjacent_points
+        adjacent
     }
 
-    fn fill_adjacent(board: &mut Vec<Vec<char>>, rows: i32, cols: i32, point: Point) -> () {
-        if board[point.0 as usize][point.1 as usize] == 'x' {
+    pub(super) fn is_revealed_at(&self, point: Point) -> bool {
+        self.player_board.get(point) == CellState::Revealed
+    }
+
+    pub(super) fn is_flagged_at(&self, point: Point) -> bool {
+        self.player_board.get(point) == CellState::Marked(Mark::Flag)
+    }
+
+    /// Number shown on a revealed safe cell.
+    pub(super) fn number_at(&self, point: Point) -> Option<u8> {
+        match (
+            self.player_board.get(point),
+            self.board.as_ref()?.get(point),
+        ) {
+            (CellState::Revealed, Cell::Empty(n)) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Minimum clicks that clear the layout (3BV): one per opening, counted once however
+    /// many zeros it has, plus one per number that does not border an opening. Openings
+    /// and numbers already revealed need no click.
+    pub fn count_bbbv(&self) -> i32 {
+        let layout = self.layout();
+        let mut cleared = Board::new(self.rows(), self.cols(), false);
+        let mut bbbv = 0;
+        for p in self.points() {
+            if cleared.get(p) || layout.get(p) != Cell::Empty(0) {
+                continue;
+            }
+            if !self.is_revealed_at(p) {
+                bbbv += 1;
+            }
+            cleared.set(p, true);
+            let mut queue = VecDeque::from([p]);
+            while let Some(q) = queue.pop_front() {
+                if layout.get(q) != Cell::Empty(0) {
+                    continue;
+                }
+                for a in self.adjacent(q) {
+                    if !cleared.get(a) {
+                        cleared.set(a, true);
+                        queue.push_back(a);
+                    }
+                }
+            }
+        }
+        let numbers = self
+            .points()
+            .filter(|p| !cleared.get(*p) && !self.is_revealed_at(*p))
+            .filter(|p| layout.get(*p) != Cell::Mine)
+            .count();
+        bbbv + numbers as i32
+    }
+
+    /// Score of a won game: 100 points per 3BV, scaled by efficiency (3BV per click, at
+    /// most 1), boosted by speed (3BV per second played) and cut by 10% per hint taken
+    /// and 20% per undo.
+    fn compute_score(&self) -> i32 {
+        let bbbv = self.bbbv as f64;
+        let efficiency = (bbbv / self.clicks.total().max(1) as f64).min(1.0);
+        let speed = bbbv / self.elapsed_seconds.max(1) as f64;
+        let penalty = 0.9f64.powi(self.hints) * 0.8f64.powi(self.undos);
+        (100.0 * bbbv * efficiency * (1.0 + speed) * penalty).round() as i32
+    }
+
+    fn layout(&self) -> &Board<Cell> {
+        self.board.as_ref().expect("board not generated")
+    }
+
+    fn fill_adjacent(&self, board: &mut Board<Cell>, point: Point) {
+        if board.get(point) != Cell::Mine {
             return;
         }
-        for p in Self::adjacent_points(rows, cols, point) {
-            let val = board[p.0 as usize][p.1 as usize];
-            if val != 'x' {
-                let digit = val.to_digit(10).unwrap() + 1;
-                board[p.0 as usize][p.1 as usize] = digit.to_string().chars().next().unwrap();
+        for p in self.adjacent(point) {
+            if let Cell::Empty(n) = board.get(p) {
+                board.set(p, Cell::Empty(n + 1));
             }
         }
     }
 
-    fn new_boards(rows: i32, cols: i32, mines: i32) -> (Vec<Vec<char>>, Vec<Vec<char>>) {
-        assert!(mines < rows * cols);
-        let player_board = vec![vec!['h'; cols as usize]; rows as usize];
-        let mut board = vec![vec!['0'; cols as usize]; rows as usize];
-        for _ in 0..mines {
-            let point = (
-                rand::thread_rng().gen_range(0..rows),
-                rand::thread_rng().gen_range(0..cols),
-            );
-            let mut mine_set = false;
-            while !mine_set {
-                if board[point.0 as usize][point.1 as usize] != 'x' {
-                    board[point.0 as usize][point.1 as usize] = 'x';
-                    mine_set = true;
-                }
+    fn new_board(&self, rng: &mut ChaCha8Rng, safe_points: &[Point]) -> Board<Cell> {
+        let mines = self.mines;
+        let mut candidates: Vec<Point> =
+            self.points().filter(|p| !safe_points.contains(p)).collect();
+        assert!(mines as usize <= candidates.len());
+        // Partial Fisher-Yates on raw `next_u64` output, so layouts don't depend on how a
+        // given `rand` release implements sampling.
+        for i in 0..mines as usize {
+            let j = i + Self::uniform(rng, (candidates.len() - i) as u64) as usize;
+            candidates.swap(i, j);
+        }
+        self.layout_with_mines(&candidates[..mines as usize])
+    }
+
+    fn uniform(rng: &mut ChaCha8Rng, bound: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let v = rng.next_u64();
+            if v < zone {
+                return v % bound;
             }
         }
-        for i in 0..rows {
-            for j in 0..cols {
-                Self::fill_adjacent(&mut board, rows, cols, Point(i, j));
+    }
+
+    pub(super) fn layout_with_mines(&self, mines: &[Point]) -> Board<Cell> {
+        let mut board = Board::new(self.rows(), self.cols(), Cell::Empty(0));
+        for point in mines {
+            board.set(*point, Cell::Mine);
+        }
+        for point in mines {
+            self.fill_adjacent(&mut board, *point);
+        }
+        board
+    }
+
+    /// Reveals `point`, flooding breadth-first through cells without adjacent mines.
+    pub fn reveal_at(&mut self, point: Point) {
+        let mut queue = VecDeque::from([point]);
+        while let Some(p) = queue.pop_front() {
+            if self.is_revealed_at(p) {
+                continue;
+            }
+            self.player_board.set(p, CellState::Revealed);
+            match self.layout().get(p) {
+                Cell::Mine => {}
+                Cell::Empty(n) => {
+                    self.safe_remaining -= 1;
+                    if n == 0 {
+                        queue.extend(self.adjacent(p));
+                    }
+                }
             }
         }
-        (board, player_board)
     }
 
-    pub fn reveal_at(&mut self, point: Point) -> () {
-        if self.player_board[point.0 as usize][point.1 as usize] == 'v' {
-            return;
+    /// Reveals the unflagged neighbors of a revealed number once it has as many flagged
+    /// neighbors as its value, returning the cells that were opened.
+    pub fn chord_at(&mut self, point: Point) -> Vec<Point> {
+        let Some(n) = self.number_at(point) else {
+            return vec![];
+        };
+        let adjacent = self.adjacent(point);
+        let flags = adjacent.iter().filter(|p| self.is_flagged_at(**p)).count();
+        if n == 0 || flags != n as usize {
+            return vec![];
         }
-        self.player_board[point.0 as usize][point.1 as usize] = 'v';
-        if self.board[point.0 as usize][point.1 as usize] == '0' {
-            for p in
-                Self::adjacent_points(self.board.len() as i32, self.board[0].len() as i32, point)
-            {
+        let mut opened = vec![];
+        for p in adjacent {
+            if !self.is_flagged_at(p) && !self.is_revealed_at(p) {
                 self.reveal_at(p);
+                opened.push(p);
             }
         }
+        opened
     }
 
     pub fn is_all_revealed(&self) -> bool {
-        for i in 0..self.board.len() {
-            for j in 0..self.board[i].len() {
-                if self.board[i][j] != 'x' && self.player_board[i][j] != 'v' {
-                    return false;
+        self.is_generated() && self.safe_remaining == 0
+    }
+
+    pub fn is_mine_at(&self, point: Point) -> bool {
+        self.layout().get(point) == Cell::Mine
+    }
+
+    /// Seconds played so far, including the running stretch since the last resume.
+    pub fn elapsed_seconds_at(&self, now: time::OffsetDateTime) -> i32 {
+        match self.resumed_timestamp {
+            Some(resumed) => self.elapsed_seconds + (now - resumed).whole_seconds() as i32,
+            None => self.elapsed_seconds,
+        }
+    }
+
+    /// Applies a player move, the only way a request changes a game. Moves are refused once
+    /// the game is over or while it is paused, marks are refused on revealed cells and
+    /// reveals on flagged ones.
+    /// Pausing or resuming a game that is not running or paused leaves it untouched.
+    pub fn apply(&mut self, action: Move, now: time::OffsetDateTime) -> Result<(), Error> {
+        match (self.state, action) {
+            (State::Started, Move::Pause) => self.pause(now),
+            (State::Paused, Move::Resume) => self.resume(now),
+            (_, Move::Pause | Move::Resume) => return Ok(()),
+            (_, Move::Undo | Move::Redo) if !self.practice => {
+                return Err(Error::UndoDisabled {
+                    id: self.id.to_string(),
+                })
+            }
+            (State::Paused, _) => {
+                return Err(Error::GamePaused {
+                    id: self.id.to_string(),
+                })
+            }
+            (State::New | State::Started | State::Won | State::Lost, Move::Undo) => {
+                self.undo(now)?
+            }
+            (State::New | State::Started | State::Won | State::Lost, Move::Redo) => {
+                self.redo(now)?
+            }
+            (State::Timeout | State::Won | State::Lost, _) => {
+                return Err(Error::GameOver {
+                    id: self.id.to_string(),
+                })
+            }
+            (State::New | State::Started, Move::Reveal(p)) => {
+                self.check_hidden(p)?;
+                if self.is_flagged_at(p) {
+                    return Err(Error::CellFlagged { point: p.into() });
+                }
+                if !self.is_generated() {
+                    self.generate_at(p)?;
+                }
+                self.clicks.reveal += 1;
+                self.start(now);
+                self.reveal_at(p);
+                self.finish_if_over(&[p], now);
+                self.record(action, now);
+            }
+            (State::New | State::Started, Move::Chord(p)) => {
+                let opened = self.chord_at(p);
+                if !opened.is_empty() {
+                    self.clicks.chord += 1;
+                    self.start(now);
+                    self.record(action, now);
+                }
+                self.finish_if_over(&opened, now);
+            }
+            (State::New | State::Started, Move::Flag(p)) => {
+                self.check_hidden(p)?;
+                self.clicks.flag += 1;
+                self.mark_flag_at(p);
+                self.record(action, now);
+            }
+            (State::New | State::Started, Move::Question(p)) => {
+                self.check_hidden(p)?;
+                self.clicks.question += 1;
+                self.mark_question_at(p);
+                self.record(action, now);
+            }
+        }
+        self.log(action.into(), now);
+        Ok(())
+    }
+
+    fn log(&mut self, event: Event, now: time::OffsetDateTime) {
+        self.events.push(Logged {
+            event,
+            created: now,
+        });
+    }
+
+    /// Brings a game read from a snapshot up to date with the events logged after it.
+    pub fn apply_events(&mut self, events: &[Logged]) -> Result<(), Error> {
+        for logged in events {
+            match logged.event {
+                Event::GameCreated => {}
+                Event::Prerevealed(p) => self.reveal_at(p),
+                Event::HintTaken => self.hints += 1,
+                Event::TimedOut => {
+                    self.check_timeout(logged.created);
+                }
+                event => {
+                    if let Some(action) = event.action() {
+                        self.apply(action, logged.created)?;
+                    }
+                }
+            }
+        }
+        self.events.clear();
+        Ok(())
+    }
+
+    /// Adds a move to the history of a practice game, dropping the undone moves it
+    /// replaces. Ranked games cannot undo, so they keep none.
+    fn record(&mut self, action: Move, now: time::OffsetDateTime) {
+        if !self.practice {
+            return;
+        }
+        self.history.retain(|m| !m.undone);
+        self.history.push(Played {
+            action,
+            created: now,
+            state: self.state,
+            undone: false,
+        });
+    }
+
+    fn undo(&mut self, now: time::OffsetDateTime) -> Result<(), Error> {
+        let Some(i) = self.history.iter().rposition(|m| !m.undone) else {
+            return Err(Error::NothingToUndo {
+                id: self.id.to_string(),
+            });
+        };
+        self.history[i].undone = true;
+        self.undos += 1;
+        self.replay(now);
+        Ok(())
+    }
+
+    fn redo(&mut self, now: time::OffsetDateTime) -> Result<(), Error> {
+        let Some(i) = self.history.iter().position(|m| m.undone) else {
+            return Err(Error::NothingToRedo {
+                id: self.id.to_string(),
+            });
+        };
+        self.history[i].undone = false;
+        self.replay(now);
+        Ok(())
+    }
+
+    /// Rebuilds the player board from the moves not undone and takes the state of the
+    /// last one, or `New` when none is left. The clock runs again when a game comes back
+    /// to `Started` and stops when it goes back to `New`.
+    fn replay(&mut self, now: time::OffsetDateTime) {
+        self.player_board = Board::new(self.rows(), self.cols(), CellState::Hidden);
+        self.safe_remaining = self.rows() * self.cols() - self.mines;
+        let played: Vec<Move> = self
+            .history
+            .iter()
+            .filter(|m| !m.undone)
+            .map(|m| m.action)
+            .collect();
+        for action in played {
+            match action {
+                Move::Reveal(p) => self.reveal_at(p),
+                Move::Chord(p) => {
+                    self.chord_at(p);
+                }
+                Move::Flag(p) => self.mark_flag_at(p),
+                Move::Question(p) => self.mark_question_at(p),
+                _ => {}
+            }
+        }
+        let state = self
+            .history
+            .iter()
+            .rfind(|m| !m.undone)
+            .map_or(State::New, |m| m.state);
+        let was_over = matches!(self.state, State::Won | State::Lost);
+        match state {
+            State::Won | State::Lost if !was_over => self.finish(state, now),
+            State::Started if self.state != State::Started => {
+                self.state = State::Started;
+                self.finished = None;
+                self.resumed_timestamp = Some(now);
+            }
+            State::New => {
+                self.stop_clock(now);
+                self.state = State::New;
+                self.finished = None;
+            }
+            _ => self.state = state,
+        }
+    }
+
+    /// Refuses hints and probabilities to race players, who compete on the same board.
+    fn check_unassisted(&self) -> Result<(), Error> {
+        match self.race {
+            Some(_) => Err(Error::AssistanceDisabled {
+                id: self.id.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Chance of a mine under each cell as far as the player can tell. Only practice games
+    /// get them, since provably safe cells read 0, and not for boards that would take too
+    /// long to work out.
+    pub fn mine_probabilities(&self) -> Result<Probabilities, Error> {
+        self.check_unassisted()?;
+        if !self.practice {
+            return Err(Error::ProbabilitiesDisabled {
+                id: self.id.to_string(),
+            });
+        }
+        probability::probabilities(self).ok_or_else(|| Error::ProbabilitiesUnavailable {
+            id: self.id.to_string(),
+        })
+    }
+
+    /// Deduces a safe cell or mine from the player view and counts it against the score.
+    pub fn take_hint(&mut self) -> Result<Option<Hint>, Error> {
+        self.check_unassisted()?;
+        match self.state {
+            State::Paused => Err(Error::GamePaused {
+                id: self.id.to_string(),
+            }),
+            State::Timeout | State::Won | State::Lost => Err(Error::GameOver {
+                id: self.id.to_string(),
+            }),
+            State::New | State::Started => {
+                let hint = solver::hint(self);
+                if hint.is_some() {
+                    self.hints += 1;
+                    self.log(Event::HintTaken, time::OffsetDateTime::now_utc());
                 }
+                Ok(hint)
             }
         }
+    }
+
+    fn check_hidden(&self, point: Point) -> Result<(), Error> {
+        if self.is_revealed_at(point) {
+            return Err(Error::CellAlreadyRevealed {
+                point: (point.0, point.1),
+            });
+        }
+        Ok(())
+    }
+
+    fn finish_if_over(&mut self, opened: &[Point], now: time::OffsetDateTime) {
+        if opened.iter().any(|p| self.is_mine_at(*p)) {
+            self.finish(State::Lost, now);
+        } else if self.is_all_revealed() {
+            self.finish(State::Won, now);
+        }
+    }
+
+    /// Starts the clock on the first move of a new game.
+    pub fn start(&mut self, now: time::OffsetDateTime) {
+        if self.state == State::New {
+            self.state = State::Started;
+            self.resumed_timestamp = Some(now);
+        }
+    }
+
+    pub fn pause(&mut self, now: time::OffsetDateTime) {
+        if self.state == State::Started {
+            self.stop_clock(now);
+            self.state = State::Paused;
+        }
+    }
+
+    pub fn resume(&mut self, now: time::OffsetDateTime) {
+        if self.state == State::Paused {
+            self.state = State::Started;
+            self.resumed_timestamp = Some(now);
+        }
+    }
+
+    /// Moves a running game whose time limit has passed to `Timeout`, returning whether it did.
+    pub fn check_timeout(&mut self, now: time::OffsetDateTime) -> bool {
+        if self.state != State::Started
+            || self.duration_seconds <= 0
+            || self.elapsed_seconds_at(now) < self.duration_seconds
+        {
+            return false;
+        }
+        self.finish(State::Timeout, now);
+        self.elapsed_seconds = self.duration_seconds;
+        self.log(Event::TimedOut, now);
         true
     }
 
-    pub fn is_mine_at(&self, point: Point) -> bool {
-        self.board[point.0 as usize][point.1 as usize] == 'x'
+    /// Ends the game in `state` (`Won`, `Lost` or `Timeout`), stopping the clock.
+    pub fn finish(&mut self, state: State, now: time::OffsetDateTime) {
+        self.stop_clock(now);
+        self.state = state;
+        self.finished = Some(now);
+        if state == State::Won {
+            self.score = self.compute_score();
+        }
     }
 
-    pub fn mark_flag_at(&mut self, point: Point) -> () {
-        self.player_board[point.0 as usize][point.1 as usize] = '!';
+    fn stop_clock(&mut self, now: time::OffsetDateTime) {
+        self.elapsed_seconds = self.elapsed_seconds_at(now);
+        self.resumed_timestamp = None;
     }
 
-    pub fn mark_question_at(&mut self, point: Point) -> () {
-        self.player_board[point.0 as usize][point.1 as usize] = '?';
+    pub fn mark_flag_at(&mut self, point: Point) {
+        if !self.is_revealed_at(point) {
+            self.player_board.set(point, CellState::Marked(Mark::Flag));
+        }
+    }
+
+    pub fn mark_question_at(&mut self, point: Point) {
+        if !self.is_revealed_at(point) {
+            self.player_board
+                .set(point, CellState::Marked(Mark::Question));
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+/// Something a player does to a game, see `Game::apply`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    Reveal(Point),
+    Chord(Point),
+    Flag(Point),
+    Question(Point),
+    Pause,
+    Resume,
+    /// Takes back the last move of a practice game.
+    Undo,
+    /// Plays again the last move taken back.
+    Redo,
+}
+
+/// A move as played, kept so practice games can step back through them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Played {
+    pub action: Move,
+    pub created: time::OffsetDateTime,
+    /// Game state right after the move.
+    pub state: State,
+    /// Taken back by an undo and waiting for a redo.
+    pub undone: bool,
+}
+
+/// Something that happened to a game. Games are stored as a snapshot plus the events
+/// logged since, see `Game::apply_events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    GameCreated,
+    /// A cell of an imported layout that starts revealed, see `Game::from_layout`.
+    Prerevealed(Point),
+    CellRevealed(Point),
+    Chorded(Point),
+    Flagged(Point),
+    Questioned(Point),
+    Paused,
+    Resumed,
+    Undone,
+    Redone,
+    HintTaken,
+    TimedOut,
+}
+
+impl From<Move> for Event {
+    fn from(action: Move) -> Self {
+        match action {
+            Move::Reveal(p) => Event::CellRevealed(p),
+            Move::Chord(p) => Event::Chorded(p),
+            Move::Flag(p) => Event::Flagged(p),
+            Move::Question(p) => Event::Questioned(p),
+            Move::Pause => Event::Paused,
+            Move::Resume => Event::Resumed,
+            Move::Undo => Event::Undone,
+            Move::Redo => Event::Redone,
+        }
+    }
+}
+
+impl Event {
+    /// The player move behind the event, if any.
+    pub fn action(self) -> Option<Move> {
+        match self {
+            Event::CellRevealed(p) => Some(Move::Reveal(p)),
+            Event::Chorded(p) => Some(Move::Chord(p)),
+            Event::Flagged(p) => Some(Move::Flag(p)),
+            Event::Questioned(p) => Some(Move::Question(p)),
+            Event::Paused => Some(Move::Pause),
+            Event::Resumed => Some(Move::Resume),
+            Event::Undone => Some(Move::Undo),
+            Event::Redone => Some(Move::Redo),
+            Event::GameCreated | Event::Prerevealed(_) | Event::HintTaken | Event::TimedOut => None,
+        }
+    }
+
+    /// Cell the event happened on, if any.
+    pub fn point(self) -> Option<Point> {
+        match self {
+            Event::Prerevealed(p)
+            | Event::CellRevealed(p)
+            | Event::Chorded(p)
+            | Event::Flagged(p)
+            | Event::Questioned(p) => Some(p),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::GameCreated => write!(f, "game_created"),
+            Event::Prerevealed(_) => write!(f, "prerevealed"),
+            Event::CellRevealed(_) => write!(f, "cell_revealed"),
+            Event::Chorded(_) => write!(f, "chorded"),
+            Event::Flagged(_) => write!(f, "flagged"),
+            Event::Questioned(_) => write!(f, "questioned"),
+            Event::Paused => write!(f, "paused"),
+            Event::Resumed => write!(f, "resumed"),
+            Event::Undone => write!(f, "undone"),
+            Event::Redone => write!(f, "redone"),
+            Event::HintTaken => write!(f, "hint_taken"),
+            Event::TimedOut => write!(f, "timed_out"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Logged {
+    pub event: Event,
+    pub created: time::OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum State {
     New = 0,
     Started = 1,
@@ -175,6 +1103,13 @@ pub enum State {
     Lost = 5,
 }
 
+impl State {
+    /// True once the game can take no more moves.
+    pub fn is_over(self) -> bool {
+        matches!(self, State::Won | State::Lost | State::Timeout)
+    }
+}
+
 impl fmt::Display for State {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -187,3 +1122,541 @@ impl fmt::Display for State {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_game_when_get_board_view_then_all_hidden() {
+        let g = Game::new(3, 5, 2);
+        assert!(!g.is_generated());
+        assert_eq!(g.get_board_view(), vec![vec![' '; 5]; 3]);
+    }
+
+    #[test]
+    fn revealed_and_marked_when_get_board_view_then_uses_legacy_chars() {
+        let mut g = Game::new(2, 3, 1);
+        g.board = Some(g.layout_with_mines(&[Point(0, 0)]));
+        g.reveal_at(Point(0, 1));
+        g.reveal_at(Point(1, 0));
+        g.mark_flag_at(Point(0, 0));
+        g.mark_question_at(Point(1, 2));
+        let view = vec![vec!['!', '1', ' '], vec!['1', ' ', '?']];
+        assert_eq!(g.get_board_view(), view);
+    }
+
+    #[test]
+    fn large_sparse_board_when_reveal_at_then_floods_without_overflow() {
+        let options = Options {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let mut g = Game::with_options(1000, 1000, 10, options);
+        let p = g.new_point((500, 500)).unwrap();
+        g.generate_at(p).unwrap();
+        g.reveal_at(p);
+        assert_eq!(g.safe_remaining, g.count_safe_remaining());
+        assert!(g.safe_remaining < 1000 * 1000 - 10);
+    }
+
+    #[test]
+    fn safe_cell_when_generate_at_then_point_has_no_mine() {
+        for _ in 0..50 {
+            let mut g = Game::new(3, 3, 8);
+            let p = g.new_point((1, 1)).unwrap();
+            g.generate_at(p).unwrap();
+            assert!(!g.is_mine_at(p));
+            assert_eq!(g.layout().get(p), Cell::Empty(8));
+        }
+    }
+
+    #[test]
+    fn safe_opening_when_generate_at_then_neighbors_have_no_mine() {
+        let options = Options {
+            safe_area: SafeArea::Opening,
+            ..Default::default()
+        };
+        for _ in 0..50 {
+            let mut g = Game::with_options(5, 5, 16, options.clone());
+            let p = g.new_point((0, 0)).unwrap();
+            g.generate_at(p).unwrap();
+            g.reveal_at(p);
+            assert_eq!(g.layout().get(p), Cell::Empty(0));
+            assert!(g.is_revealed_at(Point(1, 1)));
+        }
+    }
+
+    #[test]
+    fn same_seed_when_generate_at_then_same_layout() {
+        let options = Options {
+            seed: Some(42),
+            ..Default::default()
+        };
+        let mut g1 = Game::with_options(9, 9, 10, options.clone());
+        let mut g2 = Game::with_options(9, 9, 10, options);
+        g1.generate_at(Point(0, 0)).unwrap();
+        g2.generate_at(Point(0, 0)).unwrap();
+        assert_eq!(g1.seed, 42);
+        assert_eq!(g1.board, g2.board);
+    }
+
+    #[test]
+    fn fixed_seed_when_generate_at_then_layout_is_stable() {
+        let options = Options {
+            seed: Some(7),
+            ..Default::default()
+        };
+        let mut g = Game::with_options(4, 4, 3, options);
+        g.generate_at(Point(0, 0)).unwrap();
+        let mines = [Point(0, 1), Point(1, 0), Point(3, 2)];
+        assert_eq!(g.board, Some(g.layout_with_mines(&mines)));
+    }
+
+    #[test]
+    fn hex_topology_when_adjacent_then_six_offset_neighbors() {
+        let options = Options {
+            topology: Topology::Hex,
+            ..Default::default()
+        };
+        let g = Game::with_options(5, 5, 1, options);
+        let even = [
+            Point(1, 1),
+            Point(1, 2),
+            Point(2, 1),
+            Point(2, 3),
+            Point(3, 1),
+            Point(3, 2),
+        ];
+        let odd = [
+            Point(0, 2),
+            Point(0, 3),
+            Point(1, 1),
+            Point(1, 3),
+            Point(2, 2),
+            Point(2, 3),
+        ];
+        assert_eq!(g.adjacent(Point(2, 2)), even);
+        assert_eq!(g.adjacent(Point(1, 2)), odd);
+        assert_eq!(g.adjacent(Point(0, 0)).len(), 2);
+    }
+
+    #[test]
+    fn hex_topology_when_layout_with_mines_then_counts_hex_neighbors() {
+        let options = Options {
+            topology: Topology::Hex,
+            ..Default::default()
+        };
+        let mut g = Game::with_options(3, 3, 1, options);
+        g.board = Some(g.layout_with_mines(&[Point(1, 1)]));
+        g.reveal_at(Point(0, 0));
+        let view = g.get_board_view();
+        assert_eq!(view[0], vec!['0', '1', ' ']);
+        assert_eq!(view[1][0], '1');
+    }
+
+    #[test]
+    fn wrap_when_adjacent_then_edges_are_neighbors() {
+        let options = Options {
+            wrap: true,
+            ..Default::default()
+        };
+        let g = Game::with_options(4, 5, 1, options);
+        let mut adjacent = g.adjacent(Point(0, 0));
+        adjacent.sort();
+        let expected = [
+            Point(0, 1),
+            Point(0, 4),
+            Point(1, 0),
+            Point(1, 1),
+            Point(1, 4),
+            Point(3, 0),
+            Point(3, 1),
+            Point(3, 4),
+        ];
+        assert_eq!(adjacent, expected);
+    }
+
+    #[test]
+    fn wrap_on_narrow_board_when_layout_with_mines_then_counts_each_mine_once() {
+        let options = Options {
+            wrap: true,
+            ..Default::default()
+        };
+        let mut g = Game::with_options(2, 2, 1, options);
+        g.board = Some(g.layout_with_mines(&[Point(0, 0)]));
+        assert_eq!(g.adjacent(Point(1, 1)).len(), 3);
+        assert_eq!(g.layout().get(Point(1, 1)), Cell::Empty(1));
+    }
+
+    #[test]
+    fn paused_game_when_resume_then_accumulates_elapsed_seconds() {
+        let t0 = time::OffsetDateTime::now_utc();
+        let mut g = Game::new(3, 3, 1);
+        g.start(t0);
+        g.pause(t0 + time::Duration::seconds(10));
+        assert_eq!(g.state, State::Paused);
+        assert_eq!(g.resumed_timestamp, None);
+        assert_eq!(g.elapsed_seconds_at(t0 + time::Duration::seconds(60)), 10);
+        g.resume(t0 + time::Duration::seconds(100));
+        assert_eq!(g.state, State::Started);
+        g.finish(State::Won, t0 + time::Duration::seconds(105));
+        assert_eq!(g.elapsed_seconds, 15);
+        assert_eq!(g.resumed_timestamp, None);
+    }
+
+    #[test]
+    fn satisfied_number_when_chord_at_then_reveals_neighbors() {
+        let mut g = Game::new(3, 3, 1);
+        g.board = Some(g.layout_with_mines(&[Point(0, 0)]));
+        g.reveal_at(Point(1, 1));
+        assert!(g.chord_at(Point(1, 1)).is_empty());
+        g.mark_flag_at(Point(0, 0));
+        assert!(!g.chord_at(Point(1, 1)).is_empty());
+        assert!(g.is_all_revealed());
+    }
+
+    #[test]
+    fn unsatisfied_number_when_apply_chord_then_not_counted() {
+        let mut g = Game::new(3, 3, 1);
+        g.board = Some(g.layout_with_mines(&[Point(0, 0)]));
+        let now = time::OffsetDateTime::now_utc();
+        g.apply(Move::Reveal(Point(1, 1)), now).unwrap();
+        g.apply(Move::Chord(Point(1, 1)), now).unwrap();
+        assert_eq!(g.clicks.chord, 0);
+        g.apply(Move::Flag(Point(0, 0)), now).unwrap();
+        g.apply(Move::Chord(Point(1, 1)), now).unwrap();
+        assert_eq!(g.clicks.chord, 1);
+    }
+
+    #[test]
+    fn wrong_flag_when_chord_at_then_reveals_mine() {
+        let mut g = Game::new(3, 3, 1);
+        g.board = Some(g.layout_with_mines(&[Point(0, 0)]));
+        g.reveal_at(Point(1, 1));
+        g.mark_flag_at(Point(2, 2));
+        let opened = g.chord_at(Point(1, 1));
+        assert!(opened.iter().any(|p| g.is_mine_at(*p)));
+    }
+
+    #[test]
+    fn no_guess_when_generate_at_then_board_is_solvable() {
+        let options = Options {
+            no_guess: true,
+            ..Default::default()
+        };
+        for _ in 0..10 {
+            let mut g = Game::with_options(9, 9, 10, options.clone());
+            let p = g.new_point((4, 4)).unwrap();
+            g.generate_at(p).unwrap();
+            assert!(solver::is_solvable(&g, p));
+        }
+    }
+
+    fn outcome(mut g: Game, action: Move) -> String {
+        match g.apply(action, time::OffsetDateTime::now_utc()) {
+            Ok(()) => g.state.to_string(),
+            Err(Error::GamePaused { .. }) => "GamePaused".to_string(),
+            Err(Error::GameOver { .. }) => "GameOver".to_string(),
+            Err(Error::CellAlreadyRevealed { .. }) => "CellAlreadyRevealed".to_string(),
+            Err(Error::NothingToUndo { .. }) => "NothingToUndo".to_string(),
+            Err(Error::NothingToRedo { .. }) => "NothingToRedo".to_string(),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn every_state_when_apply_then_follows_transition_table() {
+        let moves = [
+            Move::Reveal(Point(1, 1)),
+            Move::Reveal(Point(2, 2)),
+            Move::Reveal(Point(0, 0)),
+            Move::Chord(Point(0, 0)),
+            Move::Chord(Point(0, 2)),
+            Move::Flag(Point(1, 1)),
+            Move::Flag(Point(0, 0)),
+            Move::Question(Point(1, 1)),
+            Move::Question(Point(0, 0)),
+            Move::Pause,
+            Move::Resume,
+            Move::Undo,
+            Move::Redo,
+        ];
+        let (revealed, paused, over) = ("CellAlreadyRevealed", "GamePaused", "GameOver");
+        let (undo, redo) = ("NothingToUndo", "NothingToRedo");
+        #[rustfmt::skip]
+        let table = [
+            (State::New, ["started", "lost", revealed, "new", "started", "new", revealed, "new", revealed, "new", "new", undo, redo]),
+            (State::Started, ["started", "lost", revealed, "started", "started", "started", revealed, "started", revealed, "paused", "started", undo, redo]),
+            (State::Paused, [paused, paused, paused, paused, paused, paused, paused, paused, paused, "paused", "started", paused, paused]),
+            (State::Timeout, [over, over, over, over, over, over, over, over, over, "timeout", "timeout", over, over]),
+            (State::Won, [over, over, over, over, over, over, over, over, over, "won", "won", undo, redo]),
+            (State::Lost, [over, over, over, over, over, over, over, over, over, "lost", "lost", undo, redo]),
+        ];
+        for (state, expected) in table {
+            let mut g = Game::with_options(
+                3,
+                4,
+                2,
+                Options {
+                    practice: true,
+                    ..Default::default()
+                },
+            );
+            g.board = Some(g.layout_with_mines(&[Point(2, 2), Point(0, 3)]));
+            g.player_board.set(Point(0, 0), CellState::Revealed);
+            g.player_board.set(Point(0, 2), CellState::Revealed);
+            g.player_board
+                .set(Point(0, 3), CellState::Marked(Mark::Flag));
+            g.safe_remaining = g.count_safe_remaining();
+            g.state = state;
+            for (action, expected) in moves.iter().zip(expected) {
+                assert_eq!(outcome(g.clone(), *action), expected, "{state} {action:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn ranked_game_when_undo_then_undo_disabled() {
+        let mut g = Game::new(3, 3, 1);
+        let now = time::OffsetDateTime::now_utc();
+        g.apply(Move::Flag(Point(0, 0)), now).unwrap();
+        let result = g.apply(Move::Undo, now);
+        assert!(matches!(result, Err(Error::UndoDisabled { .. })));
+        assert!(g.history.is_empty());
+    }
+
+    #[test]
+    fn practice_game_when_chord_opens_nothing_then_not_recorded() {
+        let mut g = Game::with_options(
+            3,
+            3,
+            1,
+            Options {
+                practice: true,
+                ..Default::default()
+            },
+        );
+        g.board = Some(g.layout_with_mines(&[Point(2, 2)]));
+        let now = time::OffsetDateTime::now_utc();
+        g.apply(Move::Reveal(Point(1, 1)), now).unwrap();
+        g.apply(Move::Chord(Point(1, 1)), now).unwrap();
+        assert_eq!(g.history.len(), 1);
+        g.apply(Move::Undo, now).unwrap();
+        assert_eq!(g.state, State::New);
+    }
+
+    #[test]
+    fn lost_practice_game_when_undo_then_redo_then_replays_history() {
+        let mut g = Game::with_options(
+            3,
+            3,
+            1,
+            Options {
+                practice: true,
+                ..Default::default()
+            },
+        );
+        g.board = Some(g.layout_with_mines(&[Point(2, 2)]));
+        let now = time::OffsetDateTime::now_utc();
+        g.apply(Move::Reveal(Point(1, 1)), now).unwrap();
+        g.apply(Move::Flag(Point(0, 0)), now).unwrap();
+        g.apply(Move::Reveal(Point(2, 2)), now).unwrap();
+        assert_eq!(g.state, State::Lost);
+        let lost = g.get_board_view();
+
+        g.apply(Move::Undo, now).unwrap();
+        assert_eq!(g.state, State::Started);
+        assert!(g.resumed_timestamp.is_some());
+        assert_eq!(g.get_board_view()[2][2], ' ');
+        assert_eq!(g.get_board_view()[0][0], '!');
+        assert_eq!(g.undos, 1);
+
+        g.apply(Move::Redo, now).unwrap();
+        assert_eq!(g.state, State::Lost);
+        assert_eq!(g.get_board_view(), lost);
+
+        g.apply(Move::Undo, now).unwrap();
+        g.apply(Move::Undo, now).unwrap();
+        g.apply(Move::Question(Point(0, 1)), now).unwrap();
+        assert_eq!(g.history.len(), 2);
+        assert!(matches!(
+            g.apply(Move::Redo, now),
+            Err(Error::NothingToRedo { .. })
+        ));
+    }
+
+    #[test]
+    fn practice_game_when_undo_everything_then_play_then_clock_runs() {
+        let mut g = Game::with_options(
+            3,
+            3,
+            1,
+            Options {
+                practice: true,
+                ..Default::default()
+            },
+        );
+        g.board = Some(g.layout_with_mines(&[Point(2, 2)]));
+        let now = time::OffsetDateTime::now_utc();
+        g.apply(Move::Flag(Point(0, 0)), now).unwrap();
+        g.apply(Move::Reveal(Point(1, 1)), now).unwrap();
+        g.apply(Move::Undo, now).unwrap();
+        g.apply(Move::Undo, now).unwrap();
+        assert_eq!(g.state, State::New);
+        assert!(g.resumed_timestamp.is_none());
+
+        g.apply(Move::Reveal(Point(1, 1)), now).unwrap();
+        assert_eq!(g.state, State::Started);
+        assert!(g.resumed_timestamp.is_some());
+
+        g.apply(Move::Undo, now).unwrap();
+        g.apply(Move::Redo, now).unwrap();
+        assert_eq!(g.state, State::Started);
+        assert!(g.resumed_timestamp.is_some());
+    }
+
+    #[test]
+    fn logged_events_when_apply_events_then_rebuilds_game() {
+        let mut g = Game::with_options(
+            8,
+            8,
+            10,
+            Options {
+                seed: Some(7),
+                practice: true,
+                ..Default::default()
+            },
+        );
+        let snapshot = g.clone();
+        let now = time::OffsetDateTime::now_utc();
+        g.apply(Move::Reveal(Point(4, 4)), now).unwrap();
+        g.apply(Move::Pause, now + time::Duration::seconds(5))
+            .unwrap();
+        g.apply(Move::Resume, now + time::Duration::seconds(9))
+            .unwrap();
+        g.apply(Move::Flag(Point(0, 0)), now).unwrap();
+        g.apply(Move::Undo, now).unwrap();
+        g.take_hint().unwrap();
+        assert_eq!(g.events.len(), 6);
+
+        let mut rebuilt = snapshot;
+        rebuilt.apply_events(&g.events).unwrap();
+        assert_eq!(rebuilt.get_board_view(), g.get_board_view());
+        assert_eq!(rebuilt.state, g.state);
+        assert_eq!(rebuilt.resumed_timestamp, g.resumed_timestamp);
+        assert_eq!(rebuilt.elapsed_seconds, g.elapsed_seconds);
+        assert_eq!(rebuilt.clicks, g.clicks);
+        assert_eq!(rebuilt.history, g.history);
+        assert_eq!((rebuilt.hints, rebuilt.undos), (g.hints, g.undos));
+        assert!(rebuilt.events.is_empty());
+    }
+
+    #[test]
+    fn text_layout_when_from_layout_then_numbers_and_reveals() {
+        let g = Game::from_layout("o.*\n...\n", Options::default()).unwrap();
+        assert_eq!((g.rows(), g.cols(), g.mines), (2, 3, 1));
+        assert_eq!(g.get_board_view(), [['0', '1', ' '], ['0', '1', ' ']]);
+        assert_eq!(g.state, State::New);
+        assert_eq!(g.events.len(), 1);
+
+        for layout in ["", "..\n...", "..x", "...", "o.*", "**"] {
+            assert!(matches!(
+                Game::from_layout(layout, Options::default()),
+                Err(Error::InvalidLayout { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn same_date_when_daily_then_same_layout() {
+        let date = time::Date::from_calendar_date(2026, time::Month::March, 1).unwrap();
+        let a = Game::daily(date, Difficulty::Expert, "a".to_string(), "secret");
+        let b = Game::daily(date, Difficulty::Expert, "b".to_string(), "secret");
+        let c = Game::daily(
+            date.next_day().unwrap(),
+            Difficulty::Expert,
+            "a".to_string(),
+            "secret",
+        );
+        assert_eq!(a.board, b.board);
+        assert_ne!(a.board, c.board);
+        assert_eq!(a.get_board_view(), b.get_board_view());
+        assert_eq!(a.get_board_view()[8][15], '0');
+        assert_eq!(a.state, State::New);
+    }
+
+    #[test]
+    fn daily_boards_when_created_then_bbbv_leaves_out_the_opening() {
+        let date = time::Date::from_calendar_date(2026, time::Month::March, 1).unwrap();
+        for difficulty in [
+            Difficulty::Beginner,
+            Difficulty::Intermediate,
+            Difficulty::Expert,
+        ] {
+            let g = Game::daily(date, difficulty, "a".to_string(), "secret");
+            let mut hidden = g.clone();
+            hidden.player_board = Board::new(g.rows(), g.cols(), CellState::Hidden);
+            assert_eq!(g.bbbv, g.count_bbbv());
+            assert_eq!(g.bbbv, hidden.count_bbbv() - 1, "{difficulty}");
+        }
+    }
+
+    #[test]
+    fn flagged_cell_when_apply_reveal_then_cell_flagged() {
+        let mut g = Game::new(3, 3, 1);
+        g.board = Some(g.layout_with_mines(&[Point(2, 2)]));
+        let now = time::OffsetDateTime::now_utc();
+        g.apply(Move::Flag(Point(1, 1)), now).unwrap();
+        assert!(matches!(
+            g.apply(Move::Reveal(Point(1, 1)), now),
+            Err(Error::CellFlagged { point: (1, 1) })
+        ));
+        assert!(!g.is_revealed_at(Point(1, 1)));
+        assert_eq!(g.clicks.reveal, 0);
+    }
+
+    #[test]
+    fn revealed_cell_when_mark_flag_at_then_stays_revealed() {
+        let mut g = Game::new(3, 3, 1);
+        g.board = Some(g.layout_with_mines(&[Point(2, 2)]));
+        g.reveal_at(Point(1, 1));
+        g.mark_flag_at(Point(1, 1));
+        g.mark_question_at(Point(1, 1));
+        assert!(g.is_revealed_at(Point(1, 1)));
+    }
+
+    #[test]
+    fn prerevealed_opening_when_from_layout_then_bbbv_skips_it() {
+        let g = Game::from_layout("o.*\n...\n", Options::default()).unwrap();
+        assert_eq!(g.bbbv, 1);
+        let g = Game::from_layout("..*\n...\n", Options::default()).unwrap();
+        assert_eq!(g.bbbv, 2);
+    }
+
+    #[test]
+    fn layout_when_count_bbbv_then_counts_openings_and_lone_numbers() {
+        let mut g = Game::new(3, 5, 2);
+        g.board = Some(g.layout_with_mines(&[Point(0, 2), Point(2, 4)]));
+        // 0 1 * 1 0 / 0 1 1 2 1 / 0 0 0 1 *: two openings that clear every number.
+        assert_eq!(g.count_bbbv(), 2);
+        g.board = Some(g.layout_with_mines(&[Point(0, 2), Point(2, 4), Point(0, 4)]));
+        // 0 1 * 2 * / 0 1 1 3 2 / 0 0 0 1 *: one opening, (0, 3) and (1, 4) on their own.
+        assert_eq!(g.count_bbbv(), 3);
+    }
+
+    #[test]
+    fn won_game_when_finish_then_scores_efficiency_and_speed() {
+        let mut g = Game::new(3, 3, 1);
+        g.board = Some(g.layout_with_mines(&[Point(2, 2)]));
+        g.bbbv = g.count_bbbv();
+        g.elapsed_seconds = 1;
+        g.clicks.reveal = 1;
+        g.finish(State::Won, time::OffsetDateTime::now_utc());
+        let fast = g.score;
+        g.elapsed_seconds = 10;
+        g.clicks.flag = 3;
+        g.finish(State::Won, time::OffsetDateTime::now_utc());
+        assert!(fast > g.score && g.score > 0);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::service::model::Point;
+use crate::service::solver::{components, Component, Constraint, MAX_ENUMERATION_CELLS};
+use crate::service::Game;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::collections::BTreeSet;
+
+/// Assignments drawn for a component too large to enumerate.
+const SAMPLES: usize = 500;
+/// Backtracking steps allowed for each sample before giving up on it.
+const SAMPLE_BUDGET: usize = 10_000;
+/// ChaCha stream drawn for sampling, apart from stream 0 that placed the mines.
+const SAMPLE_STREAM: u64 = 1;
+/// Steps allowed to work out the probabilities of a board, see `work`.
+const MAX_WORK: usize = 200_000_000;
+
+/// Chance of each cell holding a mine given what the player sees, aligned with
+/// `Game::get_board_view`; `None` for revealed cells.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Probabilities {
+    pub cells: Vec<Vec<Option<f64>>>,
+    /// False when some component was sampled instead of enumerated.
+    pub exact: bool,
+}
+
+/// Solutions of one component by mine count: how many there are, and for each cell how
+/// many of them put a mine there.
+struct Distribution {
+    cells: Vec<Point>,
+    solutions: Vec<f64>,
+    mines: Vec<Vec<f64>>,
+}
+
+impl Distribution {
+    fn new(component: &Component) -> Self {
+        let n = component.cells.len();
+        Self {
+            cells: component.cells.clone(),
+            solutions: vec![0.0; n + 1],
+            mines: vec![vec![0.0; n + 1]; n],
+        }
+    }
+
+    fn add(&mut self, assignment: &[bool]) {
+        let k = assignment.iter().filter(|m| **m).count();
+        self.solutions[k] += 1.0;
+        for (i, mine) in assignment.iter().enumerate() {
+            if *mine {
+                self.mines[i][k] += 1.0;
+            }
+        }
+    }
+}
+
+/// Works out mine probabilities from the board view and the total mine count. Linked
+/// frontier cells are enumerated per component and the components are combined weighting
+/// each total of frontier mines by the ways to place the rest among the other hidden cells.
+/// `None` when that would take more than `MAX_WORK` steps.
+pub(super) fn probabilities(game: &Game) -> Option<Probabilities> {
+    let view = game.get_board_view();
+    let is_unknown = |p: Point| matches!(view[p.0 as usize][p.1 as usize], ' ' | '!' | '?');
+    let mut constraints = vec![];
+    let mut shown_mines = 0;
+    for p in game.points() {
+        let c = view[p.0 as usize][p.1 as usize];
+        if c == 'x' {
+            shown_mines += 1;
+        }
+        let Some(n) = c.to_digit(10) else {
+            continue;
+        };
+        let adjacent = game.adjacent(p);
+        let cells: BTreeSet<Point> = adjacent
+            .iter()
+            .copied()
+            .filter(|a| is_unknown(*a))
+            .collect();
+        let around = adjacent
+            .iter()
+            .filter(|a| view[a.0 as usize][a.1 as usize] == 'x')
+            .count();
+        if !cells.is_empty() {
+            let mines = (n as usize).saturating_sub(around);
+            constraints.push(Constraint { cells, mines });
+        }
+    }
+
+    let components = components(&constraints);
+    let remaining = (game.mines - shown_mines).max(0) as usize;
+    if work(&components, remaining) > MAX_WORK {
+        return None;
+    }
+
+    let mut exact = true;
+    let mut rng = ChaCha8Rng::seed_from_u64(game.seed);
+    rng.set_stream(SAMPLE_STREAM);
+    let mut distributions = vec![];
+    for component in components {
+        let mut d = Distribution::new(&component);
+        if component.cells.len() <= MAX_ENUMERATION_CELLS {
+            component.enumerate(&mut |assignment| d.add(assignment));
+        } else {
+            exact = false;
+            for _ in 0..SAMPLES {
+                if let Some(assignment) = component.sample(&mut rng, SAMPLE_BUDGET) {
+                    d.add(&assignment);
+                }
+            }
+        }
+        if d.solutions.iter().any(|s| *s > 0.0) {
+            distributions.push(d);
+        }
+    }
+
+    let frontier: BTreeSet<Point> = distributions.iter().flat_map(|d| d.cells.clone()).collect();
+    let others: Vec<Point> = game
+        .points()
+        .filter(|p| is_unknown(*p) && !frontier.contains(p))
+        .collect();
+    let frontier_cells: usize = distributions.iter().map(|d| d.cells.len()).sum();
+    let max_frontier = frontier_cells.min(remaining);
+    let weights = rest_weights(others.len(), remaining, max_frontier);
+
+    let prefix = convolutions(distributions.iter(), max_frontier);
+    let mut suffix = convolutions(distributions.iter().rev(), max_frontier);
+    suffix.reverse();
+    let mut cells = vec![vec![None; game.cols() as usize]; game.rows() as usize];
+    for (c, d) in distributions.iter().enumerate() {
+        let rest = convolve(&prefix[c], &suffix[c + 1], max_frontier);
+        let scale = weigh(d.solutions.len(), &rest, &weights);
+        let total = dot(&d.solutions, &scale);
+        for (p, mines) in d.cells.iter().zip(&d.mines) {
+            let chance = if total > 0.0 {
+                dot(mines, &scale) / total
+            } else {
+                0.0
+            };
+            cells[p.0 as usize][p.1 as usize] = Some(chance);
+        }
+    }
+    let all = &prefix[distributions.len()];
+    let (mut total, mut expected) = (0.0, 0.0);
+    for (k, count) in all.iter().enumerate() {
+        total += count * weights[k];
+        expected += count * weights[k] * remaining.saturating_sub(k) as f64;
+    }
+    for p in &others {
+        let chance = if total > 0.0 {
+            expected / total / others.len() as f64
+        } else {
+            0.0
+        };
+        cells[p.0 as usize][p.1 as usize] = Some(chance);
+    }
+    Some(Probabilities { cells, exact })
+}
+
+/// Upper bound on the steps to enumerate or sample every component and to combine them
+/// by mine count, at most `remaining` of which can be on the frontier.
+fn work(components: &[Component], remaining: usize) -> usize {
+    let all: usize = components.iter().map(|c| c.cells.len()).sum();
+    let counts = all.min(remaining) + 1;
+    let (mut work, mut before) = (0usize, 0);
+    for component in components {
+        let n = component.cells.len();
+        let search = if n <= MAX_ENUMERATION_CELLS {
+            1 << n
+        } else {
+            SAMPLES * SAMPLE_BUDGET
+        };
+        let after = all - before - n;
+        // Both running convolutions, the other components' and weighing each count.
+        let combine = 2 * (n + 1) * counts
+            + (before.min(counts) + 1) * (after.min(counts) + 1)
+            + (n + 1) * counts;
+        work = work.saturating_add(search).saturating_add(combine);
+        before += n;
+    }
+    work
+}
+
+/// Solutions of the first 0, 1, 2... of `distributions` by their total mine count, up to
+/// `max` mines since more can't fit on the board.
+fn convolutions<'a>(
+    distributions: impl Iterator<Item = &'a Distribution>,
+    max: usize,
+) -> Vec<Vec<f64>> {
+    let mut all = vec![vec![1.0]];
+    for d in distributions {
+        let next = convolve(all.last().unwrap(), &d.solutions, max);
+        all.push(next);
+    }
+    all
+}
+
+/// Solutions of two sets of components by their total mine count up to `max`, scaled down
+/// so products of many components stay finite. Only ratios matter to the callers.
+fn convolve(a: &[f64], b: &[f64], max: usize) -> Vec<f64> {
+    let mut total = vec![0.0; (a.len() + b.len() - 1).min(max + 1)];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate().take(total.len().saturating_sub(i)) {
+            total[i + j] += x * y;
+        }
+    }
+    let max = total.iter().cloned().fold(0.0, f64::max);
+    if max > 0.0 {
+        total.iter_mut().for_each(|v| *v /= max);
+    }
+    total
+}
+
+/// Weight of each of the first `n` mine counts of a component, `rest[r] * weights[k + r]`
+/// summed over the mine counts `r` of the other components.
+fn weigh(n: usize, rest: &[f64], weights: &[f64]) -> Vec<f64> {
+    (0..n)
+        .map(|k| {
+            rest.iter()
+                .zip(weights.iter().skip(k))
+                .map(|(y, w)| y * w)
+                .sum()
+        })
+        .collect()
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Relative number of ways to place the mines left after `k` frontier mines among the
+/// `others` cells, C(others, remaining - k), for each k up to `max_frontier`.
+fn rest_weights(others: usize, remaining: usize, max_frontier: usize) -> Vec<f64> {
+    let mut logs = vec![f64::NEG_INFINITY; max_frontier + 1];
+    let mut log = 0.0;
+    let mut valid = false;
+    for (k, slot) in logs.iter_mut().enumerate() {
+        let Some(r) = remaining.checked_sub(k) else {
+            break;
+        };
+        if r > others {
+            continue;
+        }
+        if valid {
+            // C(n, r) / C(n, r + 1) = (r + 1) / (n - r)
+            log += ((r + 1) as f64).ln() - ((others - r) as f64).ln();
+        }
+        valid = true;
+        *slot = log;
+    }
+    let max = logs.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    if max == f64::NEG_INFINITY {
+        return vec![0.0; logs.len()];
+    }
+    logs.iter().map(|l| (l - max).exp()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::model::CellState;
+
+    fn game_from(layout: &[&str], revealed: &[Point]) -> Game {
+        let mut mines = vec![];
+        for (i, row) in layout.iter().enumerate() {
+            for (j, c) in row.chars().enumerate() {
+                if c == '*' {
+                    mines.push(Point(i as i32, j as i32));
+                }
+            }
+        }
+        let (rows, cols) = (layout.len() as i32, layout[0].len() as i32);
+        let mut g = Game::new(rows, cols, mines.len() as i32);
+        g.board = Some(g.layout_with_mines(&mines));
+        for p in revealed {
+            g.player_board.set(*p, CellState::Revealed);
+        }
+        g
+    }
+
+    #[test]
+    fn unrevealed_board_when_probabilities_then_mine_density() {
+        let g = Game::new(4, 5, 4);
+        let p = probabilities(&g).unwrap();
+        assert!(p.exact);
+        assert!(p.cells.iter().flatten().all(|c| c == &Some(0.2)));
+    }
+
+    #[test]
+    fn fifty_fifty_when_probabilities_then_half() {
+        let g = game_from(
+            &["*.", "..", ".."],
+            &[Point(1, 0), Point(1, 1), Point(2, 0), Point(2, 1)],
+        );
+        let p = probabilities(&g).unwrap();
+        assert_eq!(p.cells[0], [Some(0.5), Some(0.5)]);
+        assert_eq!(p.cells[1], [None, None]);
+    }
+
+    #[test]
+    fn zeros_beside_rest_when_probabilities_then_mines_spread_over_rest() {
+        // The revealed zeros clear the middle column, so both mines are somewhere in the
+        // left column.
+        let g = game_from(
+            &["*..", "...", "*.."],
+            &[Point(0, 2), Point(1, 2), Point(2, 2)],
+        );
+        let p = probabilities(&g).unwrap();
+        for row in &p.cells {
+            assert!((row[0].unwrap() - 2.0 / 3.0).abs() < 1e-9);
+            assert_eq!(row[1], Some(0.0));
+        }
+    }
+
+    #[test]
+    fn one_frontier_mine_when_probabilities_then_weighted_by_mine_count() {
+        // The 1 at (1, 1) sees (0, 0), (0, 1) and (0, 2); the other three hidden cells in
+        // the bottom row share the second mine.
+        let revealed = [Point(1, 0), Point(1, 1), Point(1, 2)];
+        let g = game_from(&["*..", "...", "*.."], &revealed);
+        let p = probabilities(&g).unwrap();
+        let sum: f64 = p.cells.iter().flatten().flatten().sum();
+        assert!((sum - 2.0).abs() < 1e-9);
+        assert!(p.exact);
+    }
+
+    #[test]
+    fn many_large_components_when_probabilities_then_refused() {
+        // Pairs of revealed cells split the top row into components too large to
+        // enumerate, too many to sample.
+        let top = format!("{}..", "*..".repeat(10)).repeat(50);
+        let bottom = ".".repeat(top.len());
+        let cols = top.len() as i32;
+        let mut revealed: Vec<Point> = (0..cols).map(|j| Point(1, j)).collect();
+        revealed.extend((0..cols).filter(|j| j % 32 >= 30).map(|j| Point(0, j)));
+        let g = game_from(&[&top, &bottom], &revealed);
+        assert!(probabilities(&g).is_none());
+    }
+}
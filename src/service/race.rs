@@ -0,0 +1,169 @@
+use crate::service::{Game, State};
+
+/// Games on one layout, one per player slot, that players race to clear.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Race {
+    pub id: uuid::Uuid,
+    pub created: time::OffsetDateTime,
+    pub rows: i32,
+    pub cols: i32,
+    pub mines: i32,
+    pub seed: u64,
+    /// When a player won or the last player still in lost.
+    pub finished: Option<time::OffsetDateTime>,
+    pub participants: Vec<Participant>,
+}
+
+/// A player slot of a race, open until someone joins it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Participant {
+    pub game_id: uuid::Uuid,
+    pub player: Option<String>,
+    pub joined: Option<time::OffsetDateTime>,
+}
+
+/// Where a player stands in a race, see `Race::standings`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standing {
+    pub game_id: uuid::Uuid,
+    pub player: String,
+    pub state: State,
+    /// Safe cells revealed so far, the opening included.
+    pub revealed: i32,
+    pub elapsed_seconds: i32,
+    /// When the player's game ended.
+    pub finished: Option<time::OffsetDateTime>,
+}
+
+impl Race {
+    /// A race with `players` slots and their games, all on one random layout with the
+    /// center cell revealed. Nobody picks the seed, so nobody can practice the board.
+    pub fn new(rows: i32, cols: i32, mines: i32, players: i32) -> (Self, Vec<Game>) {
+        let id = uuid::Uuid::new_v4();
+        let first = Game::race(id, rows, cols, mines);
+        let mut games = vec![first.clone()];
+        for _ in 1..players {
+            games.push(Game {
+                id: uuid::Uuid::new_v4(),
+                ..first.clone()
+            });
+        }
+        let race = Race {
+            id,
+            created: first.created,
+            rows,
+            cols,
+            mines,
+            seed: first.seed,
+            finished: None,
+            participants: games
+                .iter()
+                .map(|g| Participant {
+                    game_id: g.id,
+                    player: None,
+                    joined: None,
+                })
+                .collect(),
+        };
+        (race, games)
+    }
+
+    /// Ids of the games players joined.
+    pub fn joined(&self) -> impl Iterator<Item = uuid::Uuid> + '_ {
+        self.participants
+            .iter()
+            .filter(|p| p.player.is_some())
+            .map(|p| p.game_id)
+    }
+
+    /// True once one of `games`, those of the joined players, is won or all are lost.
+    pub fn is_decided(games: &[Game]) -> bool {
+        !games.is_empty()
+            && (games.iter().any(|g| g.state == State::Won)
+                || games.iter().all(|g| g.state.is_over()))
+    }
+
+    /// Joined players in order: winners first by when they won, then the rest by safe
+    /// cells revealed and time played up to `now`. `games` are the joined players' games.
+    pub fn standings(&self, games: &[Game], now: time::OffsetDateTime) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = self
+            .participants
+            .iter()
+            .filter_map(|p| {
+                let player = p.player.clone()?;
+                let g = games.iter().find(|g| g.id == p.game_id)?;
+                Some(Standing {
+                    game_id: g.id,
+                    player,
+                    state: g.state,
+                    revealed: g.revealed(),
+                    elapsed_seconds: g.elapsed_seconds_at(now),
+                    finished: g.finished,
+                })
+            })
+            .collect();
+        standings.sort_by_key(|s| {
+            let won = s.state == State::Won;
+            (
+                !won,
+                s.finished.filter(|_| won),
+                -s.revealed,
+                s.elapsed_seconds,
+            )
+        });
+        standings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn race_games_when_standings_then_winners_first_then_progress() {
+        let now = time::OffsetDateTime::now_utc();
+        let (mut race, mut games) = Race::new(9, 9, 10, 3);
+        assert_eq!(games.len(), 3);
+        assert!(games.iter().all(|g| g.board == games[0].board));
+        for (p, name) in race.participants.iter_mut().zip(["ann", "bob"]) {
+            p.player = Some(name.to_string());
+        }
+        assert_eq!(race.joined().count(), 2);
+        assert!(!Race::is_decided(&games[..2]));
+
+        games[0].state = State::Lost;
+        games[1].safe_remaining -= 5;
+        let standings = race.standings(&games, now);
+        assert_eq!(standings.len(), 2);
+        assert_eq!(standings[0].player, "bob");
+        assert_eq!(standings[0].revealed, standings[1].revealed + 5);
+        assert!(!Race::is_decided(&games[..2]));
+        assert!(Race::is_decided(&games[..1]));
+
+        games[1].state = State::Won;
+        games[1].safe_remaining += 10;
+        assert_eq!(race.standings(&games, now)[0].player, "bob");
+        assert!(Race::is_decided(&games[..2]));
+
+        // Of two winners the first to finish leads, even with more time on the clock.
+        games[0].state = State::Won;
+        (games[0].finished, games[0].elapsed_seconds) = (Some(now), 90);
+        (games[1].finished, games[1].elapsed_seconds) = (Some(now + time::Duration::SECOND), 30);
+        assert_eq!(race.standings(&games, now)[0].player, "ann");
+    }
+
+    #[test]
+    fn running_game_when_standings_then_counts_time_since_resumed() {
+        let now = time::OffsetDateTime::now_utc();
+        let (mut race, mut games) = Race::new(9, 9, 10, 2);
+        for (p, name) in race.participants.iter_mut().zip(["ann", "bob"]) {
+            p.player = Some(name.to_string());
+        }
+        // Both revealed as much, but ann has played longer than her snapshot shows.
+        games[0].start(now - time::Duration::seconds(100));
+        (games[1].state, games[1].elapsed_seconds) = (State::Paused, 50);
+        let standings = race.standings(&games, now);
+        assert_eq!(standings[0].player, "bob");
+        assert_eq!(standings[1].elapsed_seconds, 100);
+    }
+}
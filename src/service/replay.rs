@@ -0,0 +1,97 @@
+use crate::service::{Board, Cell, Error, Event, Game, Logged, Options};
+
+/// A game with the moves logged since its creation, to play them back one at a time.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    /// The game as it is now.
+    pub game: Game,
+    /// Logged events after the game was set up, in order.
+    pub moves: Vec<Logged>,
+    /// Events that set the game up before play.
+    setup: Vec<Logged>,
+}
+
+impl Replay {
+    pub fn new(game: Game, events: Vec<Logged>) -> Self {
+        let (setup, moves) = events
+            .into_iter()
+            .partition(|logged| matches!(logged.event, Event::GameCreated | Event::Prerevealed(_)));
+        Self { game, moves, setup }
+    }
+
+    /// Mine layout of the game, only given once it is over, see `Game::shows_layout`.
+    pub fn layout(&self) -> Option<&Board<Cell>> {
+        self.game
+            .board
+            .as_ref()
+            .filter(|_| self.game.shows_layout())
+    }
+
+    /// The game after its first `n` moves, 0 for the game as created.
+    pub fn at(&self, n: usize) -> Result<Game, Error> {
+        if n > self.moves.len() {
+            return Err(Error::NoSuchMove {
+                id: self.game.id.to_string(),
+                n,
+            });
+        }
+        let mut g = self.initial();
+        g.apply_events(&self.setup)?;
+        g.apply_events(&self.moves[..n])?;
+        Ok(g)
+    }
+
+    /// Recreates the game as it was created, with the layout it ended up with so the
+    /// replayed reveals open the same cells even if the seeded layouts changed since.
+    fn initial(&self) -> Game {
+        let game = &self.game;
+        let options = Options {
+            safe_area: game.safe_area,
+            no_guess: game.no_guess,
+            seed: Some(game.seed),
+            topology: game.topology,
+            wrap: game.wrap,
+            duration_seconds: game.duration_seconds,
+            practice: game.practice,
+        };
+        let mut g = Game::with_options(game.rows(), game.cols(), game.mines, options);
+        g.id = game.id;
+        g.created = game.created;
+        g.title = game.title.clone();
+        g.rng_version = game.rng_version;
+        if game.is_generated() {
+            g.board = game.board.clone();
+            g.bbbv = game.bbbv;
+        }
+        g
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::model::Point;
+    use crate::service::Move;
+
+    #[test]
+    fn running_game_when_replay_then_hides_layout_and_steps_moves() {
+        let mut g = Game::new(4, 4, 2);
+        g.board = Some(g.layout_with_mines(&[Point(0, 1), Point(1, 0)]));
+        let now = time::OffsetDateTime::now_utc();
+        g.apply(Move::Flag(Point(0, 0)), now).unwrap();
+        g.apply(Move::Reveal(Point(3, 3)), now).unwrap();
+        let mut events = vec![Logged {
+            event: Event::GameCreated,
+            created: g.created,
+        }];
+        events.append(&mut g.events);
+        let replay = Replay::new(g.clone(), events);
+        assert_eq!(replay.moves.len(), 2);
+        assert!(replay.layout().is_none());
+
+        assert_eq!(replay.at(0).unwrap().get_board_view()[0][0], ' ');
+        assert_eq!(replay.at(1).unwrap().get_board_view()[0][0], '!');
+        assert_eq!(replay.at(2).unwrap().get_board_view(), g.get_board_view());
+        assert!(matches!(replay.at(3), Err(Error::NoSuchMove { n: 3, .. })));
+    }
+}
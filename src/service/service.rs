@@ -1,14 +1,32 @@
-use crate::service::{Error, Game};
+use crate::service::daily;
+use crate::service::model::Point;
+use crate::service::{
+    rank, Broadcast, DailyResult, Difficulty, Error, Game, Hint, Hub, Logged, Move, Options,
+    Probabilities, Race, Replay, Standing,
+};
 use axum::async_trait;
 #[cfg(test)]
 use mockall::automock;
-
-use super::State;
+use tokio::sync::broadcast;
 
 #[cfg_attr(test, automock)]
 #[async_trait]
 pub trait ServiceTrait {
-    async fn new_game(&self, rows: i32, cols: i32, mines: i32) -> Result<Game, Error>;
+    async fn new_game(
+        &self,
+        rows: i32,
+        cols: i32,
+        mines: i32,
+        options: Options,
+    ) -> Result<Game, Error>;
+    async fn import_game(
+        &self,
+        layout: String,
+        title: String,
+        options: Options,
+    ) -> Result<Game, Error>;
+    async fn daily_game(&self, player: String, difficulty: Difficulty) -> Result<Game, Error>;
+    async fn daily_results(&self, date: time::Date) -> Result<Vec<DailyResult>, Error>;
     async fn get_game(&self, game_id: uuid::Uuid) -> Result<Game, Error>;
     async fn pause_game(&self, game_id: uuid::Uuid) -> Result<Game, Error>;
     async fn resume_game(&self, game_id: uuid::Uuid) -> Result<Game, Error>;
@@ -16,6 +34,21 @@ pub trait ServiceTrait {
     async fn mark_as_question(&self, game_id: uuid::Uuid, point: (i32, i32))
         -> Result<Game, Error>;
     async fn reveal(&self, game_id: uuid::Uuid, point: (i32, i32)) -> Result<Game, Error>;
+    async fn chord(&self, game_id: uuid::Uuid, point: (i32, i32)) -> Result<Game, Error>;
+    async fn hint(&self, game_id: uuid::Uuid) -> Result<Hint, Error>;
+    async fn probabilities(&self, game_id: uuid::Uuid) -> Result<Probabilities, Error>;
+    async fn undo_game(&self, game_id: uuid::Uuid) -> Result<Game, Error>;
+    async fn redo_game(&self, game_id: uuid::Uuid) -> Result<Game, Error>;
+    async fn replay(&self, game_id: uuid::Uuid) -> Result<Replay, Error>;
+    /// Receives every stored update of the game from now on, closed once it ends.
+    async fn subscribe(&self, game_id: uuid::Uuid)
+        -> Result<broadcast::Receiver<Broadcast>, Error>;
+    async fn new_race(&self, rows: i32, cols: i32, mines: i32, players: i32)
+        -> Result<Race, Error>;
+    async fn get_race(&self, race_id: uuid::Uuid) -> Result<Race, Error>;
+    /// Gives `player` an open slot of the race and returns its game.
+    async fn join_race(&self, race_id: uuid::Uuid, player: String) -> Result<Game, Error>;
+    async fn race_standings(&self, race_id: uuid::Uuid) -> Result<(Race, Vec<Standing>), Error>;
 }
 
 #[cfg_attr(test, automock)]
@@ -24,11 +57,28 @@ pub trait DatabaseTrait {
     async fn get(&self, game_id: uuid::Uuid) -> Result<Game, Error>;
     async fn insert(&self, value: Game) -> Result<(), Error>;
     async fn update(&self, value: Game) -> Result<(), Error>;
+    /// Every event logged for the game, oldest first.
+    async fn events(&self, game: &Game) -> Result<Vec<Logged>, Error>;
+    /// Finished daily attempts of `date`, unordered.
+    async fn daily_results(&self, date: time::Date) -> Result<Vec<DailyResult>, Error>;
+    /// Stores the race with its games, every slot open.
+    async fn insert_race(&self, race: Race, games: Vec<Game>) -> Result<(), Error>;
+    async fn get_race(&self, race_id: uuid::Uuid) -> Result<Race, Error>;
+    /// Claims an open slot of the race for `player`, returning the id of its game.
+    async fn join_race(&self, race_id: uuid::Uuid, player: String) -> Result<uuid::Uuid, Error>;
+    /// Marks the race finished unless it already is.
+    async fn finish_race(
+        &self,
+        race_id: uuid::Uuid,
+        finished: time::OffsetDateTime,
+    ) -> Result<(), Error>;
 }
 
 #[derive(Debug, Clone)]
 pub struct Service<T> {
     dependency: T,
+    hub: Hub,
+    daily_secret: String,
 }
 
 #[async_trait]
@@ -36,34 +86,68 @@ impl<T> ServiceTrait for Service<T>
 where
     T: DatabaseTrait + Sync,
 {
-    async fn new_game(&self, rows: i32, cols: i32, mines: i32) -> Result<Game, Error> {
-        let g = Game::new(rows, cols, mines);
+    async fn new_game(
+        &self,
+        rows: i32,
+        cols: i32,
+        mines: i32,
+        options: Options,
+    ) -> Result<Game, Error> {
+        let today = time::OffsetDateTime::now_utc().date();
+        if let Some(seed) = options.seed {
+            if daily::is_daily_seed(&self.daily_secret, today, seed) {
+                return Err(Error::ReservedSeed { seed });
+            }
+        }
+        let g = Game::with_options(rows, cols, mines, options);
+        self.dependency.insert(g.clone()).await?;
+        Ok(g)
+    }
+
+    async fn import_game(
+        &self,
+        layout: String,
+        title: String,
+        options: Options,
+    ) -> Result<Game, Error> {
+        let mut g = Game::from_layout(&layout, options)?;
+        g.title = title;
+        self.dependency.insert(g.clone()).await?;
+        Ok(g)
+    }
+
+    async fn daily_game(&self, player: String, difficulty: Difficulty) -> Result<Game, Error> {
+        let today = time::OffsetDateTime::now_utc().date();
+        let g = Game::daily(today, difficulty, player, &self.daily_secret);
         self.dependency.insert(g.clone()).await?;
         Ok(g)
     }
 
+    async fn daily_results(&self, date: time::Date) -> Result<Vec<DailyResult>, Error> {
+        let mut results = self.dependency.daily_results(date).await?;
+        if date >= time::OffsetDateTime::now_utc().date() {
+            results.iter_mut().for_each(|r| r.game_id = None);
+        }
+        rank(&mut results);
+        Ok(results)
+    }
+
     async fn get_game(&self, game_id: uuid::Uuid) -> Result<Game, Error> {
-        self.dependency.get(game_id).await
+        self.load(game_id).await
     }
 
     async fn pause_game(&self, game_id: uuid::Uuid) -> Result<Game, Error> {
-        self.dependency.get(game_id).await
+        let g = self.load(game_id).await?;
+        self.play(g, Move::Pause).await
     }
 
     async fn resume_game(&self, game_id: uuid::Uuid) -> Result<Game, Error> {
-        self.dependency.get(game_id).await
+        let g = self.load(game_id).await?;
+        self.play(g, Move::Resume).await
     }
 
     async fn mark_as_flag(&self, game_id: uuid::Uuid, point: (i32, i32)) -> Result<Game, Error> {
-        let mut g = self.dependency.get(game_id).await?;
-        match g.new_point(point) {
-            Some(p) => {
-                g.mark_flag_at(p);
-                self.dependency.update(g.clone()).await?;
-                Ok(g)
-            }
-            None => Err(Error::InvalidPoint { point: point }),
-        }
+        self.play_at(game_id, point, Move::Flag).await
     }
 
     async fn mark_as_question(
@@ -71,32 +155,93 @@ where
         game_id: uuid::Uuid,
         point: (i32, i32),
     ) -> Result<Game, Error> {
-        let mut g = self.dependency.get(game_id).await?;
-        match g.new_point(point) {
-            Some(p) => {
-                g.mark_question_at(p);
-                self.dependency.update(g.clone()).await?;
-                Ok(g)
-            }
-            None => Err(Error::InvalidPoint { point: point }),
-        }
+        self.play_at(game_id, point, Move::Question).await
     }
 
     async fn reveal(&self, game_id: uuid::Uuid, point: (i32, i32)) -> Result<Game, Error> {
-        let mut g = self.dependency.get(game_id).await?;
-        match g.new_point(point) {
-            Some(p) => {
-                g.reveal_at(p);
-                if g.is_mine_at(p) {
-                    g.state = State::Lost;
-                } else if g.is_all_revealed() {
-                    g.state = State::Won;
-                }
-                self.dependency.update(g.clone()).await?;
-                Ok(g)
+        self.play_at(game_id, point, Move::Reveal).await
+    }
+
+    async fn chord(&self, game_id: uuid::Uuid, point: (i32, i32)) -> Result<Game, Error> {
+        self.play_at(game_id, point, Move::Chord).await
+    }
+
+    async fn hint(&self, game_id: uuid::Uuid) -> Result<Hint, Error> {
+        let mut g = self.load(game_id).await?;
+        match g.take_hint()? {
+            Some(hint) => {
+                self.save(&g).await?;
+                Ok(hint)
             }
-            None => Err(Error::InvalidPoint { point: point }),
+            None => Err(Error::NoHint {
+                id: g.id.to_string(),
+            }),
+        }
+    }
+
+    async fn probabilities(&self, game_id: uuid::Uuid) -> Result<Probabilities, Error> {
+        let g = self.load(game_id).await?;
+        tokio::task::spawn_blocking(move || g.mine_probabilities())
+            .await
+            .expect("probabilities panicked")
+    }
+
+    async fn undo_game(&self, game_id: uuid::Uuid) -> Result<Game, Error> {
+        let g = self.load(game_id).await?;
+        self.play(g, Move::Undo).await
+    }
+
+    async fn redo_game(&self, game_id: uuid::Uuid) -> Result<Game, Error> {
+        let g = self.load(game_id).await?;
+        self.play(g, Move::Redo).await
+    }
+
+    async fn replay(&self, game_id: uuid::Uuid) -> Result<Replay, Error> {
+        let g = self.load(game_id).await?;
+        let events = self.dependency.events(&g).await?;
+        Ok(Replay::new(g, events))
+    }
+
+    async fn subscribe(
+        &self,
+        game_id: uuid::Uuid,
+    ) -> Result<broadcast::Receiver<Broadcast>, Error> {
+        let g = self.load(game_id).await?;
+        Ok(self.hub.subscribe(&g))
+    }
+
+    async fn new_race(
+        &self,
+        rows: i32,
+        cols: i32,
+        mines: i32,
+        players: i32,
+    ) -> Result<Race, Error> {
+        let (race, games) = Race::new(rows, cols, mines, players);
+        self.dependency.insert_race(race.clone(), games).await?;
+        Ok(race)
+    }
+
+    async fn get_race(&self, race_id: uuid::Uuid) -> Result<Race, Error> {
+        self.dependency.get_race(race_id).await
+    }
+
+    async fn join_race(&self, race_id: uuid::Uuid, player: String) -> Result<Game, Error> {
+        let race = self.dependency.get_race(race_id).await?;
+        if race.finished.is_some() {
+            return Err(Error::RaceFinished {
+                id: race_id.to_string(),
+            });
         }
+        let game_id = self.dependency.join_race(race_id, player).await?;
+        self.load(game_id).await
+    }
+
+    async fn race_standings(&self, race_id: uuid::Uuid) -> Result<(Race, Vec<Standing>), Error> {
+        let race = self.dependency.get_race(race_id).await?;
+        let games = self.race_games(&race).await?;
+        let standings = race.standings(&games, time::OffsetDateTime::now_utc());
+        Ok((race, standings))
     }
 }
 
@@ -105,13 +250,168 @@ where
     T: DatabaseTrait,
 {
     pub fn new(dep: T) -> Self {
-        Self { dependency: dep }
+        Self {
+            dependency: dep,
+            hub: Hub::default(),
+            daily_secret: String::new(),
+        }
+    }
+
+    /// Keys the daily challenge boards, which stay unpredictable as long as it is kept
+    /// private.
+    pub fn with_daily_secret(mut self, secret: String) -> Self {
+        self.daily_secret = secret;
+        self
+    }
+
+    /// Stores the game and passes the update on to its subscribers.
+    async fn save(&self, g: &Game) -> Result<(), Error>
+    where
+        T: Sync,
+    {
+        self.dependency.update(g.clone()).await?;
+        self.hub.publish(g);
+        Ok(())
+    }
+
+    /// Passes on the events stored for a game by another instance to its subscribers here,
+    /// if it has any.
+    pub async fn relay(&self, game_id: uuid::Uuid) -> Result<(), Error>
+    where
+        T: Sync,
+    {
+        let Some(version) = self.hub.version(game_id) else {
+            return Ok(());
+        };
+        let mut g = self.dependency.get(game_id).await?;
+        self.conceal(&mut g).await?;
+        if g.version <= version {
+            return Ok(());
+        }
+        let events = self.dependency.events(&g).await?;
+        g.events = events[(version + 1) as usize..].to_vec();
+        g.version = version;
+        self.hub.publish(&g);
+        Ok(())
+    }
+
+    async fn race_games(&self, race: &Race) -> Result<Vec<Game>, Error>
+    where
+        T: Sync,
+    {
+        let mut games = vec![];
+        for game_id in race.joined() {
+            games.push(self.dependency.get(game_id).await?);
+        }
+        Ok(games)
+    }
+
+    /// Finishes the race of a game that just ended if that decided it.
+    async fn check_race(&self, race_id: uuid::Uuid) -> Result<(), Error>
+    where
+        T: Sync,
+    {
+        let race = self.dependency.get_race(race_id).await?;
+        if race.finished.is_none() && Race::is_decided(&self.race_games(&race).await?) {
+            let now = time::OffsetDateTime::now_utc();
+            self.dependency.finish_race(race_id, now).await?;
+        }
+        Ok(())
+    }
+
+    /// Reads a game, moving it to `Timeout` first if its time limit ran out.
+    async fn load(&self, game_id: uuid::Uuid) -> Result<Game, Error>
+    where
+        T: Sync,
+    {
+        let mut g = self.dependency.get(game_id).await?;
+        let timed_out = g.check_timeout(time::OffsetDateTime::now_utc());
+        self.conceal(&mut g).await?;
+        if timed_out {
+            self.save(&g).await?;
+        }
+        Ok(g)
+    }
+
+    async fn play_at(
+        &self,
+        game_id: uuid::Uuid,
+        point: (i32, i32),
+        action: fn(Point) -> Move,
+    ) -> Result<Game, Error>
+    where
+        T: Sync,
+    {
+        let g = self.load(game_id).await?;
+        match g.new_point(point) {
+            Some(p) => self.play(g, action(p)).await,
+            None => Err(Error::InvalidPoint { point }),
+        }
+    }
+
+    /// Hides the seed and layout of a game whose board others may still be playing: daily
+    /// boards until their UTC day is over, race boards until the race is decided. Races
+    /// are only looked up for finished games, as running ones hide them anyway.
+    async fn conceal(&self, g: &mut Game) -> Result<(), Error> {
+        let today = time::OffsetDateTime::now_utc().date();
+        g.layout_secret = match (&g.daily, g.race) {
+            (Some(daily), _) => daily.date >= today,
+            (None, Some(race_id)) if g.state.is_over() => {
+                let race = self.dependency.get_race(race_id).await?;
+                race.finished.is_none()
+            }
+            (None, Some(_)) => true,
+            (None, None) => false,
+        };
+        Ok(())
+    }
+
+    /// Refuses moves in the game of a race that is already decided.
+    async fn check_race_open(&self, g: &Game) -> Result<(), Error> {
+        if let Some(race_id) = g.race {
+            let race = self.dependency.get_race(race_id).await?;
+            if race.finished.is_some() {
+                return Err(Error::RaceFinished {
+                    id: race_id.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies `action` and stores the result, skipping the write for pause and resume
+    /// requests that did not change the game. Games of a finished race take no more moves.
+    /// A move that generates a no-guess layout runs on the blocking pool, since the solver
+    /// may retry many boards.
+    async fn play(&self, mut g: Game, action: Move) -> Result<Game, Error>
+    where
+        T: Sync,
+    {
+        self.check_race_open(&g).await?;
+        let (state, now) = (g.state, time::OffsetDateTime::now_utc());
+        if g.no_guess && !g.is_generated() {
+            g = tokio::task::spawn_blocking(move || g.apply(action, now).map(|_| g))
+                .await
+                .expect("board generation panicked")?;
+        } else {
+            g.apply(action, now)?;
+        }
+        self.conceal(&mut g).await?;
+        if g.state != state || !matches!(action, Move::Pause | Move::Resume) {
+            self.save(&g).await?;
+        }
+        if let (Some(race_id), true) = (g.race, g.state.is_over()) {
+            self.check_race(race_id).await?;
+            self.conceal(&mut g).await?;
+        }
+        Ok(g)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::service::State;
 
     #[tokio::test]
     async fn existing_game_when_pause_game_returns_game() {
@@ -125,4 +425,112 @@ mod tests {
         let service = &Service::new(dep) as &dyn ServiceTrait;
         assert_eq!(service.pause_game(uid).await.unwrap().id, uid);
     }
+
+    #[tokio::test]
+    async fn started_game_when_pause_game_then_paused() {
+        let mut dep = MockDatabaseTrait::new();
+        dep.expect_get().returning(|_: uuid::Uuid| {
+            let mut g = Game::new(4, 4, 1);
+            g.start(time::OffsetDateTime::now_utc() - time::Duration::seconds(30));
+            Ok(g)
+        });
+        dep.expect_update().times(1).returning(|_: Game| Ok(()));
+        let service = &Service::new(dep) as &dyn ServiceTrait;
+        let g = service.pause_game(uuid::Uuid::new_v4()).await.unwrap();
+        assert_eq!(g.state, State::Paused);
+        assert!(g.elapsed_seconds >= 30);
+        assert_eq!(g.resumed_timestamp, None);
+    }
+
+    #[tokio::test]
+    async fn paused_game_when_reveal_then_game_paused_error() {
+        let mut dep = MockDatabaseTrait::new();
+        dep.expect_get().returning(|_: uuid::Uuid| {
+            let mut g = Game::new(4, 4, 1);
+            g.state = State::Paused;
+            Ok(g)
+        });
+        dep.expect_update().never();
+        let service = &Service::new(dep) as &dyn ServiceTrait;
+        let result = service.reveal(uuid::Uuid::new_v4(), (0, 0)).await;
+        assert!(matches!(result, Err(Error::GamePaused { .. })));
+    }
+
+    #[tokio::test]
+    async fn expired_game_when_reveal_then_timeout_and_game_over() {
+        let mut dep = MockDatabaseTrait::new();
+        dep.expect_get().returning(|_: uuid::Uuid| {
+            let mut g = Game::new(4, 4, 1);
+            g.duration_seconds = 60;
+            g.start(time::OffsetDateTime::now_utc() - time::Duration::seconds(61));
+            Ok(g)
+        });
+        dep.expect_update()
+            .times(1)
+            .withf(|g: &Game| g.state == State::Timeout && g.elapsed_seconds == 60)
+            .returning(|_: Game| Ok(()));
+        let service = &Service::new(dep) as &dyn ServiceTrait;
+        let result = service.reveal(uuid::Uuid::new_v4(), (0, 0)).await;
+        assert!(matches!(result, Err(Error::GameOver { .. })));
+    }
+
+    #[tokio::test]
+    async fn new_game_when_reveal_then_generates_safe_board() {
+        let mut dep = MockDatabaseTrait::new();
+        let uid = uuid::Uuid::new_v4();
+        dep.expect_get()
+            .returning(|_: uuid::Uuid| Ok(Game::new(2, 2, 3)));
+        dep.expect_update().returning(|_: Game| Ok(()));
+        let service = &Service::new(dep) as &dyn ServiceTrait;
+        let g = service.reveal(uid, (0, 1)).await.unwrap();
+        assert!(g.is_generated());
+        assert_eq!(g.get_board_view()[0][1], '3');
+        assert_eq!(g.state.to_string(), "won");
+    }
+
+    #[tokio::test]
+    async fn race_game_when_hint_then_assistance_disabled() {
+        let mut dep = MockDatabaseTrait::new();
+        let (race, games) = Race::new(3, 3, 1, 1);
+        let game = games[0].clone();
+        dep.expect_get()
+            .returning(move |_: uuid::Uuid| Ok(game.clone()));
+        dep.expect_get_race()
+            .returning(move |_: uuid::Uuid| Ok(race.clone()));
+        dep.expect_update().never();
+        let service = &Service::new(dep) as &dyn ServiceTrait;
+        let id = games[0].id;
+        assert!(matches!(
+            service.hint(id).await,
+            Err(Error::AssistanceDisabled { .. })
+        ));
+        assert!(matches!(
+            service.probabilities(id).await,
+            Err(Error::AssistanceDisabled { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn lost_race_game_when_replay_then_hides_layout_until_race_finished() {
+        let now = time::OffsetDateTime::now_utc();
+        for (finished, shown) in [(None, false), (Some(now), true)] {
+            let mut dep = MockDatabaseTrait::new();
+            let (race, games) = Race::new(3, 3, 1, 2);
+            let mut game = games[0].clone();
+            game.state = State::Lost;
+            dep.expect_get()
+                .returning(move |_: uuid::Uuid| Ok(game.clone()));
+            dep.expect_get_race().returning(move |_: uuid::Uuid| {
+                Ok(Race {
+                    finished,
+                    ..race.clone()
+                })
+            });
+            dep.expect_events().returning(|_| Ok(vec![]));
+            let service = &Service::new(dep) as &dyn ServiceTrait;
+            let replay = service.replay(games[0].id).await.unwrap();
+            assert_eq!(replay.layout().is_some(), shown);
+            assert_eq!(replay.game.shows_layout(), shown);
+        }
+    }
 }
@@ -0,0 +1,463 @@
+use crate::service::model::Point;
+use crate::service::Game;
+use rand::Rng;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+
+/// Largest group of linked frontier cells whose mine assignments are enumerated.
+pub(super) const MAX_ENUMERATION_CELLS: usize = 20;
+
+/// Cells whose content follows from the revealed numbers alone.
+#[derive(Debug, Default)]
+pub(super) struct Deductions {
+    pub safe: BTreeSet<Point>,
+    pub mines: BTreeSet<Point>,
+}
+
+/// Hidden neighbors of a revealed number and how many of them are mines.
+#[derive(Debug)]
+pub(super) struct Constraint {
+    pub cells: BTreeSet<Point>,
+    pub mines: usize,
+}
+
+fn constraints(game: &Game, known_mines: &BTreeSet<Point>) -> Vec<Constraint> {
+    let mut constraints = vec![];
+    for p in game.points() {
+        let Some(n) = game.number_at(p) else {
+            continue;
+        };
+        let mut cells = BTreeSet::new();
+        let mut mines = n as usize;
+        for a in game.adjacent(p) {
+            if known_mines.contains(&a) {
+                mines -= 1;
+            } else if !game.is_revealed_at(a) {
+                cells.insert(a);
+            }
+        }
+        if !cells.is_empty() {
+            constraints.push(Constraint { cells, mines });
+        }
+    }
+    constraints
+}
+
+/// Frontier cells linked by shared constraints, with each constraint as cell indexes.
+#[derive(Debug)]
+pub(super) struct Component {
+    pub cells: Vec<Point>,
+    constraints: Vec<(Vec<usize>, usize)>,
+    /// Constraints each cell takes part in.
+    links: Vec<Vec<usize>>,
+}
+
+/// Partial assignment of a component, with mines placed and cells still open per
+/// constraint so each step only rechecks the constraints of the cell it assigns.
+struct Search {
+    mines: Vec<bool>,
+    placed: Vec<usize>,
+    open: Vec<usize>,
+}
+
+impl Component {
+    fn search(&self) -> Search {
+        Search {
+            mines: vec![false; self.cells.len()],
+            placed: vec![0; self.constraints.len()],
+            open: self.constraints.iter().map(|(c, _)| c.len()).collect(),
+        }
+    }
+
+    /// Assigns cell `i` and reports whether its constraints can still be met.
+    fn set(&self, search: &mut Search, i: usize, mine: bool) -> bool {
+        search.mines[i] = mine;
+        let mut ok = true;
+        for c in &self.links[i] {
+            search.open[*c] -= 1;
+            search.placed[*c] += mine as usize;
+            let mines = self.constraints[*c].1;
+            ok &= search.placed[*c] <= mines && search.placed[*c] + search.open[*c] >= mines;
+        }
+        ok
+    }
+
+    fn unset(&self, search: &mut Search, i: usize) {
+        for c in &self.links[i] {
+            search.open[*c] += 1;
+            search.placed[*c] -= search.mines[i] as usize;
+        }
+        search.mines[i] = false;
+    }
+
+    /// Calls `found` with every mine assignment of `cells` that satisfies all constraints.
+    pub fn enumerate(&self, found: &mut impl FnMut(&[bool])) {
+        let mut search = self.search();
+        self.assign(0, &mut search, found);
+    }
+
+    fn assign(&self, i: usize, search: &mut Search, found: &mut impl FnMut(&[bool])) {
+        if i == self.cells.len() {
+            found(&search.mines);
+            return;
+        }
+        for mine in [false, true] {
+            if self.set(search, i, mine) {
+                self.assign(i + 1, search, found);
+            }
+            self.unset(search, i);
+        }
+    }
+
+    /// Looks for one satisfying assignment, trying cells in order but mine or safe in
+    /// random order, and gives up after `budget` steps.
+    pub fn sample(&self, rng: &mut impl Rng, budget: usize) -> Option<Vec<bool>> {
+        let mut search = self.search();
+        let mut steps = 0;
+        if self.sample_from(0, &mut search, rng, &mut steps, budget) {
+            return Some(search.mines);
+        }
+        None
+    }
+
+    fn sample_from(
+        &self,
+        i: usize,
+        search: &mut Search,
+        rng: &mut impl Rng,
+        steps: &mut usize,
+        budget: usize,
+    ) -> bool {
+        if i == self.cells.len() {
+            return true;
+        }
+        let first = rng.gen_bool(0.5);
+        for mine in [first, !first] {
+            *steps += 1;
+            if *steps > budget {
+                return false;
+            }
+            if self.set(search, i, mine) && self.sample_from(i + 1, search, rng, steps, budget) {
+                return true;
+            }
+            self.unset(search, i);
+        }
+        false
+    }
+}
+
+/// Splits constraints into groups that share cells. Cells are numbered in the order a
+/// breadth-first walk over the constraints meets them, so constraints close soon after
+/// they open while searching.
+pub(super) fn components(constraints: &[Constraint]) -> Vec<Component> {
+    let mut by_cell: BTreeMap<Point, Vec<usize>> = BTreeMap::new();
+    for (j, c) in constraints.iter().enumerate() {
+        for p in &c.cells {
+            by_cell.entry(*p).or_default().push(j);
+        }
+    }
+    let mut seen = vec![false; constraints.len()];
+    let mut components = vec![];
+    for i in 0..constraints.len() {
+        if seen[i] {
+            continue;
+        }
+        seen[i] = true;
+        let mut queue = VecDeque::from([i]);
+        let mut cells = vec![];
+        let mut indexes = BTreeMap::new();
+        let mut members = vec![];
+        while let Some(m) = queue.pop_front() {
+            members.push(m);
+            for p in &constraints[m].cells {
+                if !indexes.contains_key(p) {
+                    indexes.insert(*p, cells.len());
+                    cells.push(*p);
+                }
+            }
+            let linked: BTreeSet<usize> = constraints[m]
+                .cells
+                .iter()
+                .flat_map(|p| by_cell[p].iter().copied())
+                .filter(|j| !seen[*j])
+                .collect();
+            for j in linked {
+                seen[j] = true;
+                queue.push_back(j);
+            }
+        }
+        let constraints: Vec<(Vec<usize>, usize)> = members
+            .iter()
+            .map(|m| {
+                let c = &constraints[*m];
+                (c.cells.iter().map(|p| indexes[p]).collect(), c.mines)
+            })
+            .collect();
+        let mut links = vec![vec![]; cells.len()];
+        for (c, (indexes, _)) in constraints.iter().enumerate() {
+            for i in indexes {
+                links[*i].push(c);
+            }
+        }
+        components.push(Component {
+            cells,
+            constraints,
+            links,
+        });
+    }
+    components
+}
+
+type Rule = fn(&[Constraint]) -> Deductions;
+
+/// A constraint with no mines left makes its cells safe, one with as many mines as cells
+/// makes them all mines.
+fn single_cell(constraints: &[Constraint]) -> Deductions {
+    let mut deductions = Deductions::default();
+    for c in constraints {
+        if c.mines == 0 {
+            deductions.safe.extend(&c.cells);
+        } else if c.mines == c.cells.len() {
+            deductions.mines.extend(&c.cells);
+        }
+    }
+    deductions
+}
+
+/// When a constraint's cells are inside another's, the cells only in the larger one hold
+/// the difference in mines.
+fn subset(constraints: &[Constraint]) -> Deductions {
+    let mut deductions = Deductions::default();
+    for a in constraints {
+        for b in constraints {
+            if a.cells.len() >= b.cells.len() || !a.cells.is_subset(&b.cells) {
+                continue;
+            }
+            let rest = b.cells.difference(&a.cells);
+            if b.mines == a.mines {
+                deductions.safe.extend(rest);
+            } else if b.mines - a.mines == b.cells.len() - a.cells.len() {
+                deductions.mines.extend(rest);
+            }
+        }
+    }
+    deductions
+}
+
+/// Tries every assignment of small components and keeps the cells that are mines in all
+/// of them or in none.
+fn enumeration(constraints: &[Constraint]) -> Deductions {
+    let mut deductions = Deductions::default();
+    for component in components(constraints) {
+        if component.cells.len() > MAX_ENUMERATION_CELLS {
+            continue;
+        }
+        let mut solutions = 0;
+        let mut mines = vec![0; component.cells.len()];
+        component.enumerate(&mut |assignment| {
+            solutions += 1;
+            for (count, mine) in mines.iter_mut().zip(assignment) {
+                *count += *mine as usize;
+            }
+        });
+        if solutions == 0 {
+            continue;
+        }
+        for (p, count) in component.cells.iter().zip(mines) {
+            if count == 0 {
+                deductions.safe.insert(*p);
+            } else if count == solutions {
+                deductions.mines.insert(*p);
+            }
+        }
+    }
+    deductions
+}
+
+/// Applies the single-cell and subset rules to the current player view.
+pub(super) fn deduce(game: &Game, known_mines: &BTreeSet<Point>) -> Deductions {
+    let constraints = constraints(game, known_mines);
+    let mut deductions = single_cell(&constraints);
+    let mut more = subset(&constraints);
+    deductions.safe.append(&mut more.safe);
+    deductions.mines.append(&mut more.mines);
+    deductions
+}
+
+/// Rule that proved a hint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    SingleCell,
+    Subset,
+    Enumeration,
+    MineCount,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::SingleCell => write!(f, "single_cell"),
+            Reason::Subset => write!(f, "subset"),
+            Reason::Enumeration => write!(f, "enumeration"),
+            Reason::MineCount => write!(f, "mine_count"),
+        }
+    }
+}
+
+/// A cell the player can prove safe or a mine from what is on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hint {
+    pub point: (i32, i32),
+    pub mine: bool,
+    pub reason: Reason,
+}
+
+/// Finds the simplest useful deduction from the revealed numbers, preferring safe cells
+/// and skipping mines the player already flagged. Flags are never trusted as mines.
+pub(super) fn hint(game: &Game) -> Option<Hint> {
+    let rules: [(Reason, Rule); 3] = [
+        (Reason::SingleCell, single_cell),
+        (Reason::Subset, subset),
+        (Reason::Enumeration, enumeration),
+    ];
+    let mut known_mines = BTreeSet::new();
+    loop {
+        let constraints = constraints(game, &known_mines);
+        let mut found = BTreeSet::new();
+        for (reason, rule) in rules {
+            let deductions = rule(&constraints);
+            if let Some(p) = deductions.safe.first() {
+                return Some(Hint {
+                    point: (p.0, p.1),
+                    mine: false,
+                    reason,
+                });
+            }
+            if let Some(p) = deductions.mines.iter().find(|p| !game.is_flagged_at(**p)) {
+                return Some(Hint {
+                    point: (p.0, p.1),
+                    mine: true,
+                    reason,
+                });
+            }
+            found.extend(deductions.mines);
+        }
+        if known_mines.len() == game.mines as usize {
+            let hidden = game
+                .points()
+                .find(|p| !game.is_revealed_at(*p) && !known_mines.contains(p));
+            return hidden.map(|p| Hint {
+                point: (p.0, p.1),
+                mine: false,
+                reason: Reason::MineCount,
+            });
+        }
+        let before = known_mines.len();
+        known_mines.append(&mut found);
+        if known_mines.len() == before {
+            return None;
+        }
+    }
+}
+
+/// Plays a generated board from `start` without guessing and reports whether it can be won.
+pub(super) fn is_solvable(game: &Game, start: Point) -> bool {
+    let mut g = game.clone();
+    let mut known_mines = BTreeSet::new();
+    g.reveal_at(start);
+    while !g.is_all_revealed() {
+        let mut deductions = deduce(&g, &known_mines);
+        let before = known_mines.len();
+        known_mines.append(&mut deductions.mines);
+        if known_mines.len() == g.mines as usize {
+            deductions.safe.extend(
+                g.points()
+                    .filter(|p| !g.is_revealed_at(*p) && !known_mines.contains(p)),
+            );
+        }
+        if deductions.safe.is_empty() {
+            if known_mines.len() == before {
+                return false;
+            }
+            continue;
+        }
+        for p in deductions.safe {
+            g.reveal_at(p);
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::CellState;
+
+    fn game_from(layout: &[&str]) -> Game {
+        let mut mines = vec![];
+        for (i, row) in layout.iter().enumerate() {
+            for (j, c) in row.chars().enumerate() {
+                if c == '*' {
+                    mines.push(Point(i as i32, j as i32));
+                }
+            }
+        }
+        let (rows, cols) = (layout.len() as i32, layout[0].len() as i32);
+        let mut g = Game::new(rows, cols, mines.len() as i32);
+        g.board = Some(g.layout_with_mines(&mines));
+        g
+    }
+
+    #[test]
+    fn single_mine_corner_when_is_solvable_then_true() {
+        let g = game_from(&["...", "...", "..*"]);
+        assert!(is_solvable(&g, Point(0, 0)));
+    }
+
+    #[test]
+    fn mines_only_pass_when_is_solvable_then_keeps_deducing() {
+        let g = game_from(&["**.", "..*", "..."]);
+        assert!(is_solvable(&g, Point(2, 0)));
+    }
+
+    #[test]
+    fn fifty_fifty_when_is_solvable_then_false() {
+        let g = game_from(&["*.", "..", "..", "..", ".."]);
+        assert!(!is_solvable(&g, Point(4, 0)));
+    }
+
+    #[test]
+    fn one_two_one_when_hint_then_subset_finds_mine() {
+        let mut g = game_from(&["*.*", "...", "..."]);
+        g.reveal_at(Point(2, 1));
+        let hint = hint(&g).unwrap();
+        assert_eq!((hint.point, hint.mine), ((0, 0), true));
+        assert_eq!(hint.reason, Reason::Subset);
+    }
+
+    #[test]
+    fn linked_pairs_when_enumeration_then_finds_mines_subset_misses() {
+        let mut g = game_from(&["*..*", "....", "...."]);
+        for p in g.points().filter(|p| p.0 > 0).collect::<Vec<_>>() {
+            g.reveal_at(p);
+        }
+        // Row 1 reads 1 1 1 1 over four hidden cells with two mines.
+        let constraints = constraints(&g, &BTreeSet::new());
+        let deductions = enumeration(&constraints);
+        assert!(deductions.mines.contains(&Point(0, 0)));
+        assert!(deductions.mines.contains(&Point(0, 3)));
+        assert!(deductions.safe.contains(&Point(0, 1)));
+    }
+
+    #[test]
+    fn flagged_mine_when_hint_then_skips_it() {
+        let mut g = game_from(&["*..", "..."]);
+        for p in [Point(0, 1), Point(1, 0), Point(1, 1)] {
+            g.player_board.set(p, CellState::Revealed);
+        }
+        g.mark_flag_at(Point(0, 0));
+        let hint = hint(&g).unwrap();
+        assert_eq!((hint.point, hint.mine), ((0, 2), false));
+        assert_eq!(hint.reason, Reason::Subset);
+    }
+}
@@ -1,27 +1,25 @@
 use axum::Router;
 use minesweeperrust::{database::Database, handler, service::Service};
 
-const DATABASE_URL: &str = "postgresql://postgres:postgres@db:5432/minesweeper";
+pub const DATABASE_URL: &str = "postgresql://postgres:postgres@db:5432/minesweeper";
+pub const DAILY_SECRET: &str = "test-secret";
 
 pub async fn setup() -> (Router, Database) {
     let db = setup_database().await;
-    let service = Service::new(db.clone());
+    let service = Service::new(db.clone()).with_daily_secret(DAILY_SECRET.to_string());
     let handler = handler::Handler::new(service);
     let router = Router::new().merge(handler.router());
     (router, db)
 }
 
 pub async fn setup_database() -> Database {
-    let (mut client, conn) = tokio_postgres::connect(DATABASE_URL, tokio_postgres::NoTls)
-        .await
-        .unwrap();
-    tokio::spawn(async move {
-        if let Err(e) = conn.await {
-            eprintln!("connection error: {}", e);
-        }
-    });
+    let config: tokio_postgres::Config = DATABASE_URL.parse().unwrap();
+    let manager = deadpool_postgres::Manager::new(config, tokio_postgres::NoTls);
+    let pool = deadpool_postgres::Pool::builder(manager).build().unwrap();
+    let mut client = pool.get().await.unwrap();
     Database::run_migrations(&mut client)
         .await
         .expect("error running migrations");
-    Database::new(client)
+    drop(client);
+    Database::new(pool)
 }
@@ -1,7 +1,13 @@
-use ::axum_test::TestServer;
+#![allow(clippy::useless_format)]
+use ::axum_test::{TestServer, TestServerConfig};
 mod common;
+use axum::http::StatusCode;
+use minesweeperrust::database::Listener;
 use minesweeperrust::handler;
-use minesweeperrust::service::{DatabaseTrait, Game};
+use minesweeperrust::service::{
+    DatabaseTrait, Difficulty, Error, Event, Game, Logged, Move, Options, Service, ServiceTrait,
+    State,
+};
 use serde_json::json;
 use serial_test::serial;
 
@@ -14,7 +20,8 @@ async fn new_request_when_handle_new_then_returns_ok() {
         .json(&json!(handler::Create {
             rows: 4,
             columns: 4,
-            mines: 1
+            mines: 1,
+            ..Default::default()
         }))
         .await;
     resp.assert_status_ok();
@@ -32,12 +39,47 @@ async fn invalid_new_request_when_handle_new_then_returns_badrequest() {
         .json(&json!(handler::Create {
             rows: 0,
             columns: 0,
-            mines: 0
+            mines: 0,
+            ..Default::default()
         }))
         .await;
     resp.assert_status_bad_request();
 }
 
+#[tokio::test]
+async fn oversized_new_request_when_handle_new_then_returns_badrequest() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 1001,
+            columns: 4,
+            mines: 1,
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_bad_request();
+}
+
+#[tokio::test]
+async fn oversized_no_guess_request_when_handle_new_then_returns_badrequest() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 300,
+            columns: 300,
+            mines: 9000,
+            no_guess: true,
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_bad_request();
+    assert_eq!(resp.json::<handler::ErrorBody>().code, "invalid_parameters");
+}
+
 #[tokio::test]
 async fn state_request_when_handle_state_then_returns_ok() {
     let (router, _) = common::setup().await;
@@ -47,7 +89,8 @@ async fn state_request_when_handle_state_then_returns_ok() {
         .json(&json!(handler::Create {
             rows: 4,
             columns: 4,
-            mines: 1
+            mines: 1,
+            ..Default::default()
         }))
         .await;
     resp.assert_status_ok();
@@ -80,7 +123,8 @@ async fn pause_request_when_handle_pause_then_returns_ok() {
         .json(&json!(handler::Create {
             rows: 4,
             columns: 4,
-            mines: 1
+            mines: 1,
+            ..Default::default()
         }))
         .await;
     resp.assert_status_ok();
@@ -102,7 +146,8 @@ async fn resume_request_when_handle_resume_then_returns_ok() {
         .json(&json!(handler::Create {
             rows: 4,
             columns: 4,
-            mines: 1
+            mines: 1,
+            ..Default::default()
         }))
         .await;
     resp.assert_status_ok();
@@ -115,6 +160,84 @@ async fn resume_request_when_handle_resume_then_returns_ok() {
     assert_eq!(game2.id, game.id);
 }
 
+#[tokio::test]
+async fn won_game_when_handle_pause_then_stays_won() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 4,
+            columns: 4,
+            mines: 15,
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_ok();
+    let game: handler::Game = resp.json();
+    let resp2 = server
+        .post(format!("/api/v1/games/{}/mark_as_flag/", game.id).as_str())
+        .json(&json!(handler::At { x: 3, y: 3 }))
+        .await;
+    resp2.assert_status_ok();
+    assert_eq!(resp2.json::<handler::Game>().state, "new");
+    let resp3 = server
+        .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+        .json(&json!(handler::At { x: 0, y: 0 }))
+        .await;
+    resp3.assert_status_ok();
+    let game3: handler::Game = resp3.json();
+    assert_eq!(game3.state, "won");
+    assert!(game3.resumed_timestamp.is_none());
+    let resp4 = server
+        .post(format!("/api/v1/games/{}/pause/", game.id).as_str())
+        .await;
+    resp4.assert_status_ok();
+    assert_eq!(resp4.json::<handler::Game>().state, "won");
+}
+
+#[tokio::test]
+async fn started_game_when_pause_then_reveal_returns_conflict_until_resume() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 8,
+            columns: 8,
+            mines: 60,
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_ok();
+    let game: handler::Game = resp.json();
+    let resp2 = server
+        .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+        .json(&json!(handler::At { x: 0, y: 0 }))
+        .await;
+    resp2.assert_status_ok();
+    assert_eq!(resp2.json::<handler::Game>().state, "started");
+    let resp3 = server
+        .post(format!("/api/v1/games/{}/pause/", game.id).as_str())
+        .await;
+    resp3.assert_status_ok();
+    let game3: handler::Game = resp3.json();
+    assert_eq!(game3.state, "paused");
+    assert!(game3.resumed_timestamp.is_none());
+    let resp4 = server
+        .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+        .json(&json!(handler::At { x: 7, y: 7 }))
+        .await;
+    assert_eq!(resp4.status_code(), 409);
+    let resp5 = server
+        .post(format!("/api/v1/games/{}/resume/", game.id).as_str())
+        .await;
+    resp5.assert_status_ok();
+    let game5: handler::Game = resp5.json();
+    assert_eq!(game5.state, "started");
+    assert!(game5.resumed_timestamp.is_some());
+}
+
 #[tokio::test]
 async fn reveal_request_when_handle_reveal_then_returns_ok() {
     let (router, _) = common::setup().await;
@@ -124,7 +247,8 @@ async fn reveal_request_when_handle_reveal_then_returns_ok() {
         .json(&json!(handler::Create {
             rows: 4,
             columns: 4,
-            mines: 1
+            mines: 1,
+            ..Default::default()
         }))
         .await;
     resp.assert_status_ok();
@@ -147,7 +271,8 @@ async fn invalid_reveal_request_when_handle_reveal_then_returns_badrequest() {
         .json(&json!(handler::Create {
             rows: 4,
             columns: 4,
-            mines: 1
+            mines: 1,
+            ..Default::default()
         }))
         .await;
     resp.assert_status_ok();
@@ -168,7 +293,8 @@ async fn question_request_when_handle_question_then_returns_ok() {
         .json(&json!(handler::Create {
             rows: 4,
             columns: 4,
-            mines: 1
+            mines: 1,
+            ..Default::default()
         }))
         .await;
     resp.assert_status_ok();
@@ -191,7 +317,8 @@ async fn invalid_question_request_when_handle_question_then_returns_badrequest()
         .json(&json!(handler::Create {
             rows: 4,
             columns: 4,
-            mines: 1
+            mines: 1,
+            ..Default::default()
         }))
         .await;
     resp.assert_status_ok();
@@ -212,7 +339,8 @@ async fn flag_request_when_handle_flag_then_returns_ok() {
         .json(&json!(handler::Create {
             rows: 4,
             columns: 4,
-            mines: 1
+            mines: 1,
+            ..Default::default()
         }))
         .await;
     resp.assert_status_ok();
@@ -235,7 +363,8 @@ async fn invalid_flag_request_when_handle_flag_then_returns_badrequest() {
         .json(&json!(handler::Create {
             rows: 4,
             columns: 4,
-            mines: 1
+            mines: 1,
+            ..Default::default()
         }))
         .await;
     resp.assert_status_ok();
@@ -247,6 +376,227 @@ async fn invalid_flag_request_when_handle_flag_then_returns_badrequest() {
     resp2.assert_status_bad_request();
 }
 
+#[tokio::test]
+async fn safe_opening_request_when_handle_reveal_then_opens_region() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 5,
+            columns: 5,
+            mines: 10,
+            first_click: handler::FirstClick::SafeOpening,
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_ok();
+    let game: handler::Game = resp.json();
+    let resp2 = server
+        .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+        .json(&json!(handler::At { x: 2, y: 2 }))
+        .await;
+    resp2.assert_status_ok();
+    let game2: handler::Game = resp2.json();
+    assert_eq!(game2.board_view[2][2], '0');
+    assert_ne!(game2.state, "lost");
+}
+
+#[tokio::test]
+async fn no_guess_request_when_handle_reveal_then_opens_region() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 9,
+            columns: 9,
+            mines: 10,
+            no_guess: true,
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_ok();
+    let game: handler::Game = resp.json();
+    let resp2 = server
+        .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+        .json(&json!(handler::At { x: 4, y: 4 }))
+        .await;
+    resp2.assert_status_ok();
+    let game2: handler::Game = resp2.json();
+    assert_eq!(game2.board_view[4][4], '0');
+}
+
+#[tokio::test]
+async fn chord_request_when_handle_chord_then_returns_ok() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 4,
+            columns: 4,
+            mines: 1,
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_ok();
+    let game: handler::Game = resp.json();
+    let resp2 = server
+        .post(format!("/api/v1/games/{}/chord/", game.id).as_str())
+        .json(&json!(handler::At { x: 1, y: 1 }))
+        .await;
+    resp2.assert_status_ok();
+    let game2: handler::Game = resp2.json();
+    assert_eq!(game2.id, game.id);
+}
+
+#[tokio::test]
+async fn invalid_chord_request_when_handle_chord_then_returns_badrequest() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 4,
+            columns: 4,
+            mines: 1,
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_ok();
+    let game: handler::Game = resp.json();
+    let resp2 = server
+        .post(format!("/api/v1/games/{}/chord/", game.id).as_str())
+        .json(&json!(handler::At { x: 4, y: 1 }))
+        .await;
+    resp2.assert_status_bad_request();
+}
+
+#[tokio::test]
+async fn seeded_requests_when_handle_reveal_then_same_board() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let mut views = vec![];
+    for _ in 0..2 {
+        let resp = server
+            .post("/api/v1/games/new/")
+            .json(&json!(handler::Create {
+                rows: 8,
+                columns: 8,
+                mines: 10,
+                seed: Some(1234),
+                ..Default::default()
+            }))
+            .await;
+        resp.assert_status_ok();
+        let game: handler::Game = resp.json();
+        let resp2 = server
+            .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+            .json(&json!(handler::At { x: 0, y: 0 }))
+            .await;
+        resp2.assert_status_ok();
+        let game2: handler::Game = resp2.json();
+        views.push(game2.board_view);
+    }
+    assert_eq!(views[0], views[1]);
+}
+
+#[tokio::test]
+async fn live_game_when_handle_state_then_hides_seed_until_over() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 1,
+            columns: 2,
+            mines: 1,
+            seed: Some(1234),
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_ok();
+    let game: handler::Game = resp.json();
+    assert_eq!(game.seed, 0);
+    let resp2 = server
+        .get(format!("/api/v1/games/{}/state/", game.id).as_str())
+        .await;
+    resp2.assert_status_ok();
+    assert_eq!(resp2.json::<handler::Game>().seed, 0);
+    let resp3 = server
+        .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+        .json(&json!(handler::At { x: 0, y: 0 }))
+        .await;
+    resp3.assert_status_ok();
+    let game3: handler::Game = resp3.json();
+    assert_eq!(game3.state, "won");
+    assert_eq!(game3.seed, 1234);
+}
+
+#[tokio::test]
+async fn hex_request_when_handle_new_then_returns_hex_layout() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 6,
+            columns: 6,
+            mines: 4,
+            topology: handler::Topology::Hex,
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_ok();
+    let game: handler::Game = resp.json();
+    let resp2 = server
+        .get(format!("/api/v1/games/{}/state/", game.id).as_str())
+        .await;
+    resp2.assert_status_ok();
+    let game2: handler::Game = resp2.json();
+    assert_eq!(game2.layout.topology, "hex");
+    assert_eq!(game2.layout.neighbors, 6);
+    assert_eq!(game2.layout.offset, Some("odd-r".to_string()));
+}
+
+#[tokio::test]
+async fn wrap_request_when_handle_new_then_returns_wrapping_layout() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 6,
+            columns: 6,
+            mines: 4,
+            wrap: true,
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_ok();
+    let game: handler::Game = resp.json();
+    assert!(game.layout.wrap);
+}
+
+#[tokio::test]
+async fn odd_rows_hex_wrap_request_when_handle_new_then_returns_badrequest() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 5,
+            columns: 6,
+            mines: 4,
+            topology: handler::Topology::Hex,
+            wrap: true,
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_bad_request();
+}
+
 #[tokio::test]
 #[serial]
 async fn insert_game_when_database_get_returns_game() {
@@ -258,6 +608,7 @@ async fn insert_game_when_database_get_returns_game() {
 
     let read_game = database.get(game.id).await.unwrap();
     assert_eq!(game.title, read_game.title);
+    assert_eq!(game.seed, read_game.seed);
 
     game.title = "Hello from Dependency and test".to_string();
     assert!(database.update(game.clone()).await.is_ok());
@@ -265,3 +616,819 @@ async fn insert_game_when_database_get_returns_game() {
     let read_game = database.get(game.id).await.unwrap();
     assert_eq!(game.title, read_game.title);
 }
+
+#[tokio::test]
+#[serial]
+async fn expired_game_when_database_timeout_expired_then_timeout() {
+    let database = common::setup_database().await;
+
+    let mut game = Game::new(4, 4, 1);
+    game.duration_seconds = 10;
+    game.start(time::OffsetDateTime::now_utc() - time::Duration::seconds(11));
+    assert!(database.insert(game.clone()).await.is_ok());
+
+    assert!(database.timeout_expired().await.unwrap() >= 1);
+    let read_game = database.get(game.id).await.unwrap();
+    assert_eq!(read_game.state, State::Timeout);
+    assert_eq!(read_game.elapsed_seconds, 10);
+    assert_eq!(read_game.resumed_timestamp, None);
+}
+
+#[tokio::test]
+#[serial]
+async fn paused_after_snapshot_when_database_timeout_expired_then_stays_paused() {
+    let database = common::setup_database().await;
+
+    let now = time::OffsetDateTime::now_utc();
+    let mut game = Game::new(4, 4, 1);
+    game.duration_seconds = 10;
+    game.start(now - time::Duration::seconds(11));
+    database.insert(game.clone()).await.unwrap();
+    let mut game = database.get(game.id).await.unwrap();
+    game.apply(Move::Pause, now - time::Duration::seconds(6))
+        .unwrap();
+    database.update(game.clone()).await.unwrap();
+
+    database.timeout_expired().await.unwrap();
+    let read_game = database.get(game.id).await.unwrap();
+    assert_eq!(read_game.state, State::Paused);
+    assert_eq!(read_game.elapsed_seconds, 5);
+}
+
+#[tokio::test]
+async fn timed_request_when_handle_new_then_returns_duration() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 4,
+            columns: 4,
+            mines: 1,
+            duration_seconds: 120,
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_ok();
+    let game: handler::Game = resp.json();
+    assert_eq!(game.duration_seconds, 120);
+}
+
+#[tokio::test]
+async fn won_game_when_handle_reveal_then_returns_gone() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 4,
+            columns: 4,
+            mines: 15,
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_ok();
+    let game: handler::Game = resp.json();
+    let resp2 = server
+        .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+        .json(&json!(handler::At { x: 0, y: 0 }))
+        .await;
+    resp2.assert_status_ok();
+    let game2: handler::Game = resp2.json();
+    assert_eq!(game2.state, "won");
+    assert_eq!(game2.bbbv, 1);
+    assert_eq!(game2.clicks.reveal, 1);
+    assert!(game2.score > 0);
+    let resp3 = server
+        .post(format!("/api/v1/games/{}/mark_as_flag/", game.id).as_str())
+        .json(&json!(handler::At { x: 1, y: 1 }))
+        .await;
+    resp3.assert_status(StatusCode::GONE);
+}
+
+#[tokio::test]
+async fn invalid_requests_when_handle_then_return_error_body() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server.get("/api/v1/games/not-a-uuid/state/").await;
+    resp.assert_status_bad_request();
+    let error: handler::ErrorBody = resp.json();
+    assert_eq!(error.code, "invalid_id");
+    assert_eq!(error.details.id, Some("not-a-uuid".to_string()));
+
+    let resp2 = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 4,
+            columns: 4,
+            mines: 1,
+            ..Default::default()
+        }))
+        .await;
+    let game: handler::Game = resp2.json();
+    let resp3 = server
+        .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+        .json(&json!(handler::At { x: 7, y: 1 }))
+        .await;
+    resp3.assert_status_bad_request();
+    let error3: handler::ErrorBody = resp3.json();
+    assert_eq!(error3.code, "invalid_point");
+    assert_eq!(error3.details.point.map(|p| (p.x, p.y)), Some((7, 1)));
+
+    let resp4 = server
+        .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+        .json(&json!({ "x": "one" }))
+        .await;
+    resp4.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(resp4.json::<handler::ErrorBody>().code, "invalid_body");
+}
+
+#[tokio::test]
+async fn started_game_when_handle_hint_then_returns_deduction() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 1,
+            columns: 3,
+            mines: 1,
+            seed: Some(4),
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_ok();
+    let game: handler::Game = resp.json();
+    let resp2 = server
+        .get(format!("/api/v1/games/{}/hint/", game.id).as_str())
+        .await;
+    resp2.assert_status_not_found();
+    assert_eq!(resp2.json::<handler::ErrorBody>().code, "no_hint");
+    let resp3 = server
+        .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+        .json(&json!(handler::At { x: 0, y: 0 }))
+        .await;
+    resp3.assert_status_ok();
+    assert_eq!(resp3.json::<handler::Game>().board_view, [['1', ' ', ' ']]);
+    let resp4 = server
+        .get(format!("/api/v1/games/{}/hint/", game.id).as_str())
+        .await;
+    resp4.assert_status_ok();
+    let hint: handler::Hint = resp4.json();
+    assert_eq!((hint.x, hint.y, hint.mine), (1, 0, true));
+    assert_eq!(hint.reason, "single_cell");
+    let resp5 = server
+        .get(format!("/api/v1/games/{}/state/", game.id).as_str())
+        .await;
+    assert_eq!(resp5.json::<handler::Game>().hints, 1);
+}
+
+#[tokio::test]
+async fn new_practice_game_when_handle_probabilities_then_returns_density() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 4,
+            columns: 5,
+            mines: 5,
+            practice: true,
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_ok();
+    let game: handler::Game = resp.json();
+    let resp2 = server
+        .get(format!("/api/v1/games/{}/probabilities/", game.id).as_str())
+        .await;
+    resp2.assert_status_ok();
+    let probabilities: handler::Probabilities = resp2.json();
+    assert!(probabilities.exact);
+    assert_eq!(probabilities.probabilities.len(), 4);
+    assert!(probabilities
+        .probabilities
+        .iter()
+        .flatten()
+        .all(|p| p == &Some(0.25)));
+}
+
+#[tokio::test]
+async fn ranked_game_when_handle_probabilities_then_returns_forbidden() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 4,
+            columns: 5,
+            mines: 5,
+            ..Default::default()
+        }))
+        .await;
+    let game: handler::Game = resp.json();
+    let resp2 = server
+        .get(format!("/api/v1/games/{}/probabilities/", game.id).as_str())
+        .await;
+    resp2.assert_status(StatusCode::FORBIDDEN);
+    assert_eq!(
+        resp2.json::<handler::ErrorBody>().code,
+        "probabilities_disabled"
+    );
+}
+
+#[tokio::test]
+async fn practice_game_when_handle_undo_then_reverts_move() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 4,
+            columns: 4,
+            mines: 1,
+            practice: true,
+            ..Default::default()
+        }))
+        .await;
+    let game: handler::Game = resp.json();
+    server
+        .post(format!("/api/v1/games/{}/mark_as_flag/", game.id).as_str())
+        .json(&json!(handler::At { x: 0, y: 0 }))
+        .await
+        .assert_status_ok();
+    let resp2 = server
+        .post(format!("/api/v1/games/{}/undo/", game.id).as_str())
+        .await;
+    resp2.assert_status_ok();
+    let game2: handler::Game = resp2.json();
+    assert_eq!(game2.board_view[0][0], ' ');
+    assert_eq!(game2.undos, 1);
+    server
+        .post(format!("/api/v1/games/{}/redo/", game.id).as_str())
+        .await
+        .assert_status_ok();
+    let resp3 = server
+        .get(format!("/api/v1/games/{}/state/", game.id).as_str())
+        .await;
+    assert_eq!(resp3.json::<handler::Game>().board_view[0][0], '!');
+    let resp4 = server
+        .post(format!("/api/v1/games/{}/redo/", game.id).as_str())
+        .await;
+    resp4.assert_status(StatusCode::CONFLICT);
+    assert_eq!(resp4.json::<handler::ErrorBody>().code, "nothing_to_redo");
+}
+
+#[tokio::test]
+async fn ranked_game_when_handle_undo_then_returns_forbidden() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 4,
+            columns: 4,
+            mines: 1,
+            ..Default::default()
+        }))
+        .await;
+    let game: handler::Game = resp.json();
+    let resp2 = server
+        .post(format!("/api/v1/games/{}/undo/", game.id).as_str())
+        .await;
+    resp2.assert_status(StatusCode::FORBIDDEN);
+    assert_eq!(resp2.json::<handler::ErrorBody>().code, "undo_disabled");
+}
+
+#[tokio::test]
+#[serial]
+async fn logged_moves_when_database_get_then_rebuilds_from_snapshot() {
+    let database = common::setup_database().await;
+
+    let mut game = Game::new(5, 5, 3);
+    database.insert(game.clone()).await.unwrap();
+    let now = time::OffsetDateTime::now_utc();
+    for i in 0..22 {
+        let p = game.new_point((i % 5, i / 5)).unwrap();
+        let action = if i % 2 == 0 {
+            Move::Flag(p)
+        } else {
+            Move::Question(p)
+        };
+        game.apply(action, now).unwrap();
+        database.update(game.clone()).await.unwrap();
+        game = database.get(game.id).await.unwrap();
+    }
+    // The snapshot taken after 20 events has no layout yet; the first reveal places the
+    // mines and is snapshotted right away.
+    game.apply(Move::Reveal(game.new_point((4, 4)).unwrap()), now)
+        .unwrap();
+    database.update(game.clone()).await.unwrap();
+
+    let read_game = database.get(game.id).await.unwrap();
+    assert_eq!(read_game.version, 23);
+    assert!(read_game.events.is_empty());
+    assert_eq!(read_game.board, game.board);
+    assert_eq!(read_game.get_board_view(), game.get_board_view());
+    assert_eq!(read_game.clicks, game.clicks);
+    assert_eq!(read_game.state, game.state);
+}
+
+#[tokio::test]
+async fn concurrent_moves_when_database_update_then_write_conflict() {
+    let database = common::setup_database().await;
+    let game = Game::new(3, 3, 1);
+    database.insert(game.clone()).await.unwrap();
+    let mut first = database.get(game.id).await.unwrap();
+    let mut second = first.clone();
+    let now = time::OffsetDateTime::now_utc();
+    first
+        .apply(Move::Flag(game.new_point((0, 0)).unwrap()), now)
+        .unwrap();
+    second
+        .apply(Move::Flag(game.new_point((1, 1)).unwrap()), now)
+        .unwrap();
+
+    database.update(first).await.unwrap();
+    assert!(matches!(
+        database.update(second).await,
+        Err(Error::WriteConflict { .. })
+    ));
+    let read_game = database.get(game.id).await.unwrap();
+    let view = read_game.get_board_view();
+    assert_eq!((view[0][0], view[1][1]), ('!', ' '));
+}
+
+#[tokio::test]
+async fn won_game_when_handle_replay_then_returns_layout_and_frames() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 1,
+            columns: 3,
+            mines: 1,
+            seed: Some(4),
+            ..Default::default()
+        }))
+        .await;
+    let game: handler::Game = resp.json();
+    server
+        .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+        .json(&json!(handler::At { x: 0, y: 0 }))
+        .await
+        .assert_status_ok();
+    let resp2 = server
+        .get(format!("/api/v1/games/{}/replay/", game.id).as_str())
+        .await;
+    resp2.assert_status_ok();
+    assert_eq!(resp2.json::<handler::Replay>().mine_layout, None);
+
+    server
+        .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+        .json(&json!(handler::At { x: 2, y: 0 }))
+        .await
+        .assert_status_ok();
+    let resp3 = server
+        .get(format!("/api/v1/games/{}/replay/", game.id).as_str())
+        .await;
+    let replay: handler::Replay = resp3.json();
+    assert_eq!(replay.mine_layout, Some(vec![".*.".to_string()]));
+    assert_eq!(replay.moves.len(), 2);
+    assert_eq!(replay.moves[1].kind, "cell_revealed");
+    assert_eq!(
+        replay.moves[1].at.as_ref().map(|p| (p.x, p.y)),
+        Some((2, 0))
+    );
+
+    let resp4 = server
+        .get(format!("/api/v1/games/{}/replay/at/1/", game.id).as_str())
+        .await;
+    resp4.assert_status_ok();
+    let frame: handler::ReplayFrame = resp4.json();
+    assert_eq!(frame.board_view, [['1', ' ', ' ']]);
+    assert_eq!(frame.state, "started");
+    let resp5 = server
+        .get(format!("/api/v1/games/{}/replay/at/3/", game.id).as_str())
+        .await;
+    resp5.assert_status_not_found();
+    assert_eq!(resp5.json::<handler::ErrorBody>().code, "no_such_move");
+}
+
+#[tokio::test]
+async fn text_layout_when_handle_import_then_returns_game() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/import/")
+        .json(&json!(handler::Import {
+            layout: "o.*\n...\n".to_string(),
+            title: Some("Tutorial".to_string()),
+            ..Default::default()
+        }))
+        .await;
+    resp.assert_status_ok();
+    let game: handler::Game = resp.json();
+    assert_eq!(game.title, "Tutorial");
+    assert_eq!(game.board_view, [['0', '1', ' '], ['0', '1', ' ']]);
+
+    let resp2 = server
+        .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+        .json(&json!(handler::At { x: 2, y: 1 }))
+        .await;
+    resp2.assert_status_ok();
+    assert_eq!(resp2.json::<handler::Game>().state, "won");
+
+    let resp3 = server
+        .post("/api/v1/games/import/")
+        .json(&json!(handler::Import {
+            layout: "..#\n".to_string(),
+            ..Default::default()
+        }))
+        .await;
+    resp3.assert_status_bad_request();
+    assert_eq!(resp3.json::<handler::ErrorBody>().code, "invalid_layout");
+}
+
+#[tokio::test]
+async fn long_title_when_handle_import_then_returns_badrequest() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let import = |title: String| {
+        server
+            .post("/api/v1/games/import/")
+            .json(&json!(handler::Import {
+                layout: "o.*\n...\n".to_string(),
+                title: Some(title),
+                ..Default::default()
+            }))
+    };
+    import("t".repeat(255)).await.assert_status_ok();
+    let resp = import("t".repeat(256)).await;
+    resp.assert_status_bad_request();
+    assert_eq!(resp.json::<handler::ErrorBody>().code, "invalid_parameters");
+}
+
+#[tokio::test]
+async fn daily_game_when_handle_daily_then_one_attempt_per_player() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let player = format!("player-{}", uuid::Uuid::new_v4());
+    let resp = server
+        .post("/api/v1/games/daily/")
+        .json(&json!(handler::NewDaily {
+            player: player.clone(),
+            difficulty: handler::Difficulty::Beginner,
+        }))
+        .await;
+    resp.assert_status_ok();
+    let game: handler::Game = resp.json();
+    let daily = game.daily.clone().unwrap();
+    assert_eq!(
+        (daily.player.as_str(), daily.difficulty.as_str()),
+        (player.as_str(), "beginner")
+    );
+    assert_eq!(game.board_view[4][4], '0');
+
+    let resp2 = server
+        .post("/api/v1/games/daily/")
+        .json(&json!(handler::NewDaily {
+            player: player.clone(),
+            difficulty: handler::Difficulty::Beginner,
+        }))
+        .await;
+    resp2.assert_status(StatusCode::CONFLICT);
+    assert_eq!(
+        resp2.json::<handler::ErrorBody>().code,
+        "daily_already_played"
+    );
+
+    let resp3 = server
+        .post("/api/v1/games/daily/")
+        .json(&json!(handler::NewDaily {
+            player: format!("other-{}", uuid::Uuid::new_v4()),
+            difficulty: handler::Difficulty::Beginner,
+        }))
+        .await;
+    assert_eq!(resp3.json::<handler::Game>().board_view, game.board_view);
+
+    let today = time::OffsetDateTime::now_utc().date();
+    let layout = Game::daily(
+        today,
+        Difficulty::Beginner,
+        player.clone(),
+        common::DAILY_SECRET,
+    );
+    let mine = (0..9 * 9)
+        .map(|i| (i / 9, i % 9))
+        .find(|p| layout.is_mine_at(layout.new_point(*p).unwrap()))
+        .unwrap();
+    server
+        .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+        .json(&json!(handler::At {
+            x: mine.1,
+            y: mine.0
+        }))
+        .await
+        .assert_status_ok();
+    let resp4 = server
+        .get(format!("/api/v1/daily/{}/results/", daily.date).as_str())
+        .await;
+    resp4.assert_status_ok();
+    let results: handler::DailyResults = resp4.json();
+    let result = results.results.iter().find(|r| r.player == player).unwrap();
+    assert_eq!(
+        (result.state.as_str(), result.game_id.clone()),
+        ("lost", None)
+    );
+
+    server
+        .get("/api/v1/daily/2026-02-30/results/")
+        .await
+        .assert_status_bad_request();
+
+    let resp5 = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 9,
+            columns: 9,
+            mines: 10,
+            seed: Some(layout.seed),
+            ..Default::default()
+        }))
+        .await;
+    resp5.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(resp5.json::<handler::ErrorBody>().code, "reserved_seed");
+}
+
+#[tokio::test]
+async fn lost_daily_attempt_when_handle_replay_then_hides_layout_and_game_until_day_over() {
+    let (router, database) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let today = time::OffsetDateTime::now_utc().date();
+    let yesterday = today.previous_day().unwrap();
+    for (date, shown) in [(today, false), (yesterday, true)] {
+        let player = format!("player-{}", uuid::Uuid::new_v4());
+        let game = Game::daily(
+            date,
+            Difficulty::Beginner,
+            player.clone(),
+            common::DAILY_SECRET,
+        );
+        database.insert(game.clone()).await.unwrap();
+        let mut game = database.get(game.id).await.unwrap();
+        let mine = (0..9 * 9)
+            .map(|i| game.new_point((i / 9, i % 9)).unwrap())
+            .find(|p| game.is_mine_at(*p))
+            .unwrap();
+        game.apply(Move::Reveal(mine), time::OffsetDateTime::now_utc())
+            .unwrap();
+        database.update(game.clone()).await.unwrap();
+
+        let resp = server
+            .get(format!("/api/v1/games/{}/replay/", game.id).as_str())
+            .await;
+        resp.assert_status_ok();
+        let replay: handler::Replay = resp.json();
+        assert_eq!(replay.mine_layout.is_some(), shown);
+        let resp2 = server
+            .get(format!("/api/v1/games/{}/state/", game.id).as_str())
+            .await;
+        let state: handler::Game = resp2.json();
+        assert_eq!(state.state, "lost");
+        assert_eq!(state.seed != 0, shown);
+        let resp3 = server
+            .get(format!("/api/v1/daily/{date}/results/").as_str())
+            .await;
+        let results: handler::DailyResults = resp3.json();
+        let result = results.results.iter().find(|r| r.player == player).unwrap();
+        assert_eq!(result.game_id.is_some(), shown);
+    }
+}
+
+#[tokio::test]
+async fn socket_commands_when_handle_ws_then_pushes_updates() {
+    let (router, _) = common::setup().await;
+    let config = TestServerConfig::builder().http_transport().build();
+    let server = TestServer::new_with_config(router, config).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 1,
+            columns: 3,
+            mines: 1,
+            seed: Some(4),
+            ..Default::default()
+        }))
+        .await;
+    let game: handler::Game = resp.json();
+    let mut socket = server
+        .get_websocket(format!("/api/v1/games/{}/ws", game.id).as_str())
+        .await
+        .into_websocket()
+        .await;
+
+    socket
+        .send_json(&json!({ "action": "reveal", "x": 0, "y": 0 }))
+        .await;
+    let handler::Push::Update(update) = socket.receive_json().await else {
+        panic!("expected an update");
+    };
+    assert_eq!(update.game.board_view, [['1', ' ', ' ']]);
+    assert_eq!(
+        update.changed,
+        [handler::Changed {
+            x: 0,
+            y: 0,
+            value: '1'
+        }]
+    );
+
+    socket
+        .send_json(&json!({ "action": "flag", "x": 0, "y": 0 }))
+        .await;
+    let handler::Push::Error(error) = socket.receive_json().await else {
+        panic!("expected an error");
+    };
+    assert_eq!(error.code, "cell_already_revealed");
+
+    socket.send_text("reveal everything").await;
+    let handler::Push::Error(error) = socket.receive_json().await else {
+        panic!("expected an error");
+    };
+    assert_eq!(error.code, "invalid_message");
+
+    let resp2 = server
+        .get(format!("/api/v1/games/{}/state/", game.id).as_str())
+        .await;
+    assert_eq!(resp2.json::<handler::Game>().board_view, [['1', ' ', ' ']]);
+}
+
+#[tokio::test]
+async fn finished_game_when_handle_events_then_streams_moves_and_ends() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/games/new/")
+        .json(&json!(handler::Create {
+            rows: 1,
+            columns: 3,
+            mines: 1,
+            seed: Some(4),
+            ..Default::default()
+        }))
+        .await;
+    let game: handler::Game = resp.json();
+    let events = server.get(format!("/api/v1/games/{}/events/", game.id).as_str());
+    let moves = async {
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        for x in [0, 2] {
+            server
+                .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+                .json(&json!(handler::At { x, y: 0 }))
+                .await
+                .assert_status_ok();
+        }
+    };
+    let (resp2, _) = tokio::join!(events, moves);
+    resp2.assert_status_ok();
+    let spectated: Vec<handler::Spectated> = resp2
+        .text()
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(|data| serde_json::from_str(data).unwrap())
+        .collect();
+    assert_eq!(spectated.len(), 2);
+    assert_eq!(spectated[0].kind, "cell_revealed");
+    assert_eq!(spectated[0].game.seed, 0);
+    assert_eq!(spectated[1].game.state, "won");
+    assert_eq!(spectated[1].game.seed, 4);
+
+    server
+        .get(format!("/api/v1/games/{}/events/", uuid::Uuid::new_v4()).as_str())
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn move_on_other_instance_when_relay_then_reaches_local_subscribers() {
+    let db = common::setup_database().await;
+    let (local, other) = (Service::new(db.clone()), Service::new(db.clone()));
+    let mut listener = Listener::connect(common::DATABASE_URL).await.unwrap();
+    let g = other
+        .new_game(
+            1,
+            3,
+            1,
+            Options {
+                seed: Some(4),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    let mut receiver = local.subscribe(g.id).await.unwrap();
+
+    other.reveal(g.id, (0, 0)).await.unwrap();
+    while let Some(id) = listener.recv().await {
+        local.relay(id).await.unwrap();
+        if id == g.id && !receiver.is_empty() {
+            break;
+        }
+    }
+    let update = receiver.recv().await.unwrap();
+    assert_eq!(update.game.get_board_view(), [['1', ' ', ' ']]);
+    assert!(matches!(
+        update.events[..],
+        [Logged {
+            event: Event::CellRevealed(_),
+            ..
+        }]
+    ));
+}
+
+#[tokio::test]
+async fn race_players_when_one_wins_then_race_finishes_and_ranks_winner_first() {
+    let (router, _) = common::setup().await;
+    let server = TestServer::new(router).unwrap();
+    let resp = server
+        .post("/api/v1/races/")
+        .json(&json!(handler::NewRace {
+            rows: 1,
+            columns: 3,
+            mines: 1,
+            players: 2,
+        }))
+        .await;
+    resp.assert_status_ok();
+    let race: handler::Race = resp.json();
+    assert_eq!((race.players, race.participants.len()), (2, 0));
+
+    let join = |player: &str| {
+        server
+            .post(format!("/api/v1/races/{}/join/", race.id).as_str())
+            .json(&json!(handler::JoinRace {
+                player: player.to_string()
+            }))
+    };
+    let ann: handler::Game = join("ann").await.json();
+    assert_eq!(ann.board_view, [[' ', '1', ' ']]);
+    assert_eq!(ann.race.as_deref(), Some(race.id.as_str()));
+    let resp2 = join("ann").await;
+    resp2.assert_status(StatusCode::CONFLICT);
+    assert_eq!(
+        resp2.json::<handler::ErrorBody>().code,
+        "player_already_joined"
+    );
+    let bob: handler::Game = join("bob").await.json();
+    assert_eq!(bob.board_view, ann.board_view);
+    assert_eq!(
+        join("cy").await.json::<handler::ErrorBody>().code,
+        "race_full"
+    );
+    let lobby: handler::Race = server
+        .get(format!("/api/v1/races/{}/", race.id).as_str())
+        .await
+        .json();
+    assert!(lobby.participants.iter().all(|p| p.game_id.is_none()));
+    let resp3 = server
+        .get(format!("/api/v1/games/{}/hint/", ann.id).as_str())
+        .await;
+    resp3.assert_status(StatusCode::FORBIDDEN);
+    assert_eq!(
+        resp3.json::<handler::ErrorBody>().code,
+        "assistance_disabled"
+    );
+
+    let reveal = |game: &handler::Game, x: i32| {
+        server
+            .post(format!("/api/v1/games/{}/reveal/", game.id).as_str())
+            .json(&json!(handler::At { x, y: 0 }))
+    };
+    // The mine is on either side of the opening: ann tries the left, bob then the right.
+    let winners = match reveal(&ann, 0).await.json::<handler::Game>().state.as_str() {
+        "won" => ["ann", "bob"],
+        _ => {
+            assert_eq!(reveal(&bob, 2).await.json::<handler::Game>().state, "won");
+            ["bob", "ann"]
+        }
+    };
+
+    let standings: handler::Standings = server
+        .get(format!("/api/v1/races/{}/standings/", race.id).as_str())
+        .await
+        .json();
+    assert!(standings.finished.is_some());
+    let players: Vec<_> = standings
+        .standings
+        .iter()
+        .map(|s| s.player.as_str())
+        .collect();
+    assert_eq!(players, winners);
+    assert_eq!(standings.standings[0].revealed, 2);
+    assert!(standings.standings.iter().all(|s| s.game_id.is_some()));
+    let resp4 = join("cy").await;
+    resp4.assert_status(StatusCode::GONE);
+    assert_eq!(resp4.json::<handler::ErrorBody>().code, "race_finished");
+}